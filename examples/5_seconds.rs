@@ -9,23 +9,23 @@ fn main() {
 
     let mut drpc = Client::new(1003450375732482138);
 
-    drpc.on_ready(|_ctx| {
+    drpc.on_ready(|_ready| {
         println!("ready?");
     })
     .persist();
 
-    drpc.on_activity_join_request(|ctx| {
-        println!("Join request: {:?}", ctx.event);
+    drpc.on_activity_join_request(|request| {
+        println!("Join request: {:?}", request);
     })
     .persist();
 
-    drpc.on_activity_join(|ctx| {
-        println!("Joined: {:?}", ctx.event);
+    drpc.on_activity_join(|join| {
+        println!("Joined: {:?}", join);
     })
     .persist();
 
-    drpc.on_activity_spectate(|ctx| {
-        println!("Spectate: {:?}", ctx.event);
+    drpc.on_activity_spectate(|spectate| {
+        println!("Spectate: {:?}", spectate);
     })
     .persist();
 
@@ -33,7 +33,7 @@ fn main() {
 
     drpc.block_until_event(Event::Ready).unwrap();
 
-    assert!(Client::is_ready());
+    assert!(drpc.is_ready());
 
     // Set the activity
     drpc.set_activity(|act| {
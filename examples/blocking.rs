@@ -7,23 +7,23 @@ fn main() -> anyhow::Result<()> {
 
     let mut drpc = Client::new(1003450375732482138);
 
-    drpc.on_ready(|_ctx| {
+    drpc.on_ready(|_ready| {
         println!("ready?");
     })
     .persist();
 
-    drpc.on_activity_join_request(|ctx| {
-        println!("Join request: {:?}", ctx.event);
+    drpc.on_activity_join_request(|request| {
+        println!("Join request: {:?}", request);
     })
     .persist();
 
-    drpc.on_activity_join(|ctx| {
-        println!("Joined: {:?}", ctx.event);
+    drpc.on_activity_join(|join| {
+        println!("Joined: {:?}", join);
     })
     .persist();
 
-    drpc.on_activity_spectate(|ctx| {
-        println!("Spectate: {:?}", ctx.event);
+    drpc.on_activity_spectate(|spectate| {
+        println!("Spectate: {:?}", spectate);
     })
     .persist();
 
@@ -31,7 +31,7 @@ fn main() -> anyhow::Result<()> {
 
     drpc.block_until_event(Event::Ready)?;
 
-    assert!(Client::is_ready());
+    assert!(drpc.is_ready());
 
     // Set the activity
     drpc.set_activity(|act| {
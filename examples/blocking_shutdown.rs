@@ -7,7 +7,7 @@ fn main() -> anyhow::Result<()> {
 
     let mut drpc = Client::new(1003450375732482138);
 
-    drpc.on_ready(|_ctx| {
+    drpc.on_ready(|_ready| {
         println!("ready?");
     })
     .persist();
@@ -16,7 +16,7 @@ fn main() -> anyhow::Result<()> {
 
     drpc.block_until_event(Event::Ready)?;
 
-    assert!(Client::is_ready());
+    assert!(drpc.is_ready());
 
     // Set the activity
     // drpc.set_activity(|act| {
@@ -7,13 +7,13 @@ fn main() {
 
     let mut drpc = Client::new(1003450375732482138);
 
-    drpc.on_ready(|_ctx| {
+    drpc.on_ready(|_ready| {
         println!("READY!");
     })
     .persist();
 
-    drpc.on_error(|ctx| {
-        eprintln!("An error occured, {:?}", ctx.event);
+    drpc.on_error(|error| {
+        eprintln!("An error occured, {:?}", error);
     })
     .persist();
 
@@ -7,20 +7,20 @@ fn main() {
 
     let mut drpc = Client::new(1003450375732482138);
 
-    let _ready = drpc.on_ready(|_ctx| {
+    let _ready = drpc.on_ready(|_ready| {
         println!("ready?");
     });
 
-    let _activity_join_request = drpc.on_activity_join_request(|ctx| {
-        println!("Join request: {:?}", ctx.event);
+    let _activity_join_request = drpc.on_activity_join_request(|request| {
+        println!("Join request: {:?}", request);
     });
 
-    let _activity_join = drpc.on_activity_join(|ctx| {
-        println!("Joined: {:?}", ctx.event);
+    let _activity_join = drpc.on_activity_join(|join| {
+        println!("Joined: {:?}", join);
     });
 
-    let _activity_spectate = drpc.on_activity_spectate(|ctx| {
-        println!("Spectate: {:?}", ctx.event);
+    let _activity_spectate = drpc.on_activity_spectate(|spectate| {
+        println!("Spectate: {:?}", spectate);
     });
 
     drpc.start();
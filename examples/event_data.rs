@@ -1,4 +1,4 @@
-use discord_presence::{models::EventData, Client, Event};
+use discord_presence::{Client, Event};
 
 fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt()
@@ -7,12 +7,8 @@ fn main() -> anyhow::Result<()> {
 
     let mut drpc = Client::new(1003450375732482138);
 
-    drpc.on_ready(|ctx| {
-        let EventData::Ready(data) = ctx.event else {
-            unreachable!()
-        };
-
-        let _user = data.user;
+    drpc.on_ready(|ready| {
+        let _user = ready.user;
     })
     .persist();
 
@@ -20,7 +16,7 @@ fn main() -> anyhow::Result<()> {
 
     drpc.block_until_event(Event::Ready)?;
 
-    assert!(Client::is_ready());
+    assert!(drpc.is_ready());
 
     // Set the activity
     drpc.set_activity(|act| {
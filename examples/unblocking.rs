@@ -15,7 +15,7 @@ fn main() {
     {
         let ready = client.on_ready({
             let client = client.clone();
-            move |_ctx| {
+            move |_ready| {
                 let mut client = client.clone();
                 println!("READY!");
 
@@ -36,8 +36,8 @@ fn main() {
 
     // an alternative is to store the handle until you're ready to unregister the
     // listener
-    let _error = client.on_error(|ctx| {
-        eprintln!("An error occured, {:?}", ctx.event);
+    let _error = client.on_error(|error| {
+        eprintln!("An error occured, {:?}", error);
     });
 
     tracing::trace!("Made it to the final line");
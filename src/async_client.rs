@@ -0,0 +1,1201 @@
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+
+use crate::{
+    client::{ClientBuilder, AUTHORIZE_TIMEOUT},
+    connection::{handshake_message, Connection, ConnectionState, Socket},
+    dispatch::DispatchMode,
+    event_handler::{Context as EventContext, EventCallbackHandle, HandlerRegistry},
+    event_stream::{Events, OverflowPolicy, DEFAULT_EVENT_CAPACITY},
+    models::{
+        commands::{AuthenticateResponse, AuthorizeArgs, Subscription, SubscriptionArgs},
+        guild::{Channel, Guild, PartialChannel, PartialGuild},
+        message::Message,
+        payload::Payload,
+        rich_presence::{Activity, SetActivityArgs},
+        voice::{SelectTextChannelArgs, SelectVoiceChannelArgs, UserVoiceSettings, VoiceSettings},
+        ActivityInviteEvent, ActivityJoinEvent, ActivityJoinRequestEvent, ActivitySpectateEvent,
+        ChannelCreateEvent, ConnectionStateEvent, ErrorEvent, Event, EventData, GuildCreateEvent,
+        GuildStatusEvent, MessageEvent, NotificationCreateEvent, OpCode, PartialUser, ReadyEvent,
+        SpeakingEvent, VoiceConnectionStatusEvent, VoiceStateEvent,
+    },
+    request::{self, Request},
+    DiscordError, Result,
+};
+use bytes::BytesMut;
+use crossbeam_channel::RecvTimeoutError;
+use parking_lot::Mutex;
+use serde_json::Value;
+use tokio::{
+    io::{split, AsyncRead, AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf},
+    sync::{oneshot, Mutex as AsyncMutex},
+    task::JoinHandle,
+};
+
+cfg_if::cfg_if! {
+    if #[cfg(unix)] {
+        type Stream = tokio::net::UnixStream;
+
+        async fn connect_to(path: &Path) -> std::io::Result<Stream> {
+            Stream::connect(path).await
+        }
+    } else if #[cfg(windows)] {
+        type Stream = tokio::net::windows::named_pipe::NamedPipeClient;
+
+        async fn connect_to(path: &Path) -> std::io::Result<Stream> {
+            tokio::net::windows::named_pipe::ClientOptions::new().open(path)
+        }
+    }
+}
+
+/// State shared between all clones of an [`AsyncClient`] and its reader task
+struct Shared {
+    connection_state: Mutex<ConnectionState>,
+    /// Commands waiting for a response, by nonce
+    pending: Mutex<HashMap<String, oneshot::Sender<Message>>>,
+    event_handler_registry: Arc<HandlerRegistry>,
+}
+
+impl Shared {
+    /// Moves the connection to a new state, firing the appropriate events
+    fn set_connection_state(&self, current: ConnectionState) {
+        let previous = std::mem::replace(&mut *self.connection_state.lock(), current);
+
+        if previous != current {
+            // The client only ever starts out connected
+            self.event_handler_registry
+                .connection_state_changed(previous, current, true);
+        }
+    }
+
+    /// Routes a message from Discord to the command waiting for it, or to the event handlers
+    fn handle_message(&self, msg: Message) -> Result<()> {
+        request::route_message(msg, &self.event_handler_registry, |nonce, msg| {
+            let Some(tx) = self.pending.lock().remove(nonce) else {
+                return false;
+            };
+
+            // The caller may have given up waiting already
+            let _ = tx.send(msg);
+            true
+        })
+    }
+}
+
+/// Aborts the reader task once the last clone of the client is dropped
+struct ReaderTask(JoinHandle<()>);
+
+impl Drop for ReaderTask {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Reads from the stream until a complete frame is buffered, keeping any bytes after it for the next call
+async fn recv<R>(reader: &mut R, buffer: &mut BytesMut) -> Result<Message>
+where
+    R: AsyncRead + Unpin,
+{
+    loop {
+        if let Some(message) = Message::decode_frame(buffer)? {
+            trace!("<- {:?}", message);
+            return Ok(message);
+        }
+
+        if reader.read_buf(buffer).await? == 0 {
+            return Err(DiscordError::ConnectionClosed);
+        }
+    }
+}
+
+/// Receives messages until the connection is closed, without ever blocking the runtime
+async fn read_loop(mut reader: ReadHalf<Stream>, mut buffer: BytesMut, shared: Arc<Shared>) {
+    loop {
+        match recv(&mut reader, &mut buffer).await {
+            Ok(Message {
+                opcode: OpCode::Close,
+                payload,
+            }) => {
+                debug!("Discord closed the connection: {}", payload);
+                break;
+            }
+            Ok(message) => {
+                if let Err(why) = shared.handle_message(message) {
+                    error!("Failed to handle message: {}", why);
+                }
+            }
+            Err(why) => {
+                debug!("Connection lost: {}", why);
+                break;
+            }
+        }
+    }
+
+    // Dropping the senders fails any command still waiting for a response
+    shared.pending.lock().clear();
+
+    if *shared.connection_state.lock() != ConnectionState::ShuttingDown {
+        shared.set_connection_state(ConnectionState::Disconnected);
+    }
+
+    // The client never reconnects, so no more events will arrive
+    shared.event_handler_registry.close_streams();
+}
+
+impl ClientBuilder {
+    /// Connects an [`AsyncClient`] to Discord, returning once the handshake has completed
+    ///
+    /// The async client does not reconnect, so the reconnect policy and session restore options are ignored,
+    /// and it closes the connection right away when shut down, so the shutdown options are ignored too.
+    /// Transcripts are neither recorded nor replayed, and the [`connector`](ClientBuilder::connector) is not used.
+    /// Event handlers are always called inline, on the task reading from Discord, whatever the dispatch mode.
+    /// Once the connection is lost, commands fail with [`DiscordError::ConnectionClosed`],
+    /// and a new client has to be connected.
+    ///
+    /// # Errors
+    /// - Discord is not running, or could not be found
+    /// - Discord rejected the handshake
+    /// - [`DiscordError::TimeoutError`] if Discord did not answer the handshake within the command timeout
+    pub async fn connect_async(self) -> Result<AsyncClient> {
+        let mut last_error = None;
+        let mut connection = None;
+
+        for (path, slot) in Socket::candidates(&self.connection) {
+            match connect_to(&path).await {
+                Ok(stream) => {
+                    trace!("Connected to {}", path.display());
+                    connection = Some((stream, slot));
+                    break;
+                }
+                Err(why) => {
+                    trace!("Failed to connect to {}: {}", path.display(), why);
+                    last_error = Some(why.into());
+                }
+            }
+        }
+
+        let Some((stream, slot)) = connection else {
+            return Err(last_error.unwrap_or(DiscordError::ConnectionClosed));
+        };
+
+        let (mut reader, mut writer) = split(stream);
+        let mut buffer = BytesMut::new();
+
+        trace!("Performing handshake");
+        let handshake = async {
+            writer
+                .write_all(&handshake_message(self.client_id)?.encode()?)
+                .await?;
+
+            recv(&mut reader, &mut buffer).await
+        };
+
+        // A socket that accepts connections but never answers would otherwise hang forever
+        let Ok(response) = tokio::time::timeout(self.command_timeout, handshake).await else {
+            return Err(RecvTimeoutError::Timeout.into());
+        };
+        let response = response?;
+        if response.opcode == OpCode::Close {
+            error!("Discord rejected the handshake: {}", response.payload);
+            return Err(DiscordError::ConnectionClosed);
+        }
+        let payload: Payload<Value> = serde_json::from_str(&response.payload)?;
+        let ready_data = match Event::Ready.parse_data(into_error!(payload.data)?) {
+            EventData::Ready(ready) => Some(ready),
+            _ => None,
+        };
+        trace!("Handshake completed");
+
+        let shared = Arc::new(Shared {
+            connection_state: Mutex::new(ConnectionState::Connected),
+            pending: Mutex::new(HashMap::new()),
+            event_handler_registry: Arc::new(HandlerRegistry::with_dispatch_mode(
+                DispatchMode::Inline,
+            )),
+        });
+        let reader = tokio::spawn(read_loop(reader, buffer, shared.clone()));
+
+        Ok(AsyncClient {
+            writer: Arc::new(AsyncMutex::new(writer)),
+            shared,
+            reader: Arc::new(ReaderTask(reader)),
+            application_id: self.client_id,
+            slot,
+            command_timeout: self.command_timeout,
+            ready_data,
+        })
+    }
+}
+
+/// A Discord client for tokio, connected over a single socket
+///
+/// Messages from Discord are read by a task on the runtime, rather than a dedicated thread,
+/// so event handlers are called on that task and must not block.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use discord_presence::AsyncClient;
+/// # async fn run() -> discord_presence::Result<()> {
+/// let drpc = AsyncClient::connect(1003450375732482138).await?;
+///
+/// drpc.set_activity(|act| act.state("rusting")).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct AsyncClient {
+    writer: Arc<AsyncMutex<WriteHalf<Stream>>>,
+    shared: Arc<Shared>,
+    reader: Arc<ReaderTask>,
+    application_id: u64,
+    slot: Option<u8>,
+    command_timeout: Duration,
+    ready_data: Option<ReadyEvent>,
+}
+
+impl AsyncClient {
+    /// Connects to Discord with the default options, see [`ClientBuilder::connect_async`]
+    ///
+    /// # Errors
+    /// - Discord is not running, or could not be found
+    /// - Discord rejected the handshake
+    pub async fn connect(client_id: u64) -> Result<Self> {
+        ClientBuilder::new(client_id).connect_async().await
+    }
+
+    #[must_use]
+    /// The current state of the connection to Discord
+    ///
+    /// Either [`ConnectionState::Connected`], [`ConnectionState::Disconnected`] once the connection is lost,
+    /// or [`ConnectionState::ShuttingDown`] after [`AsyncClient::shutdown`].
+    pub fn connection_state(&self) -> ConnectionState {
+        *self.shared.connection_state.lock()
+    }
+
+    #[must_use]
+    /// The IPC slot (`discord-ipc-N`) the client is connected on
+    ///
+    /// Always `None` when connected through [`ClientBuilder::ipc_path`].
+    pub fn ipc_slot(&self) -> Option<u8> {
+        self.slot
+    }
+
+    #[must_use]
+    /// The data Discord sent when the handshake completed
+    pub fn ready_data(&self) -> Option<ReadyEvent> {
+        self.ready_data.clone()
+    }
+
+    #[must_use]
+    /// The user logged into Discord
+    pub fn current_user(&self) -> Option<PartialUser> {
+        self.ready_data().and_then(|ready| ready.user)
+    }
+
+    /// Closes the connection, failing any commands still waiting for a response
+    ///
+    /// # Errors
+    /// - The socket could not be shut down cleanly
+    pub async fn shutdown(self) -> Result<()> {
+        self.shared
+            .set_connection_state(ConnectionState::ShuttingDown);
+        self.reader.0.abort();
+        self.shared.pending.lock().clear();
+        self.shared.event_handler_registry.close_streams();
+        self.writer.lock().await.shutdown().await?;
+
+        Ok(())
+    }
+
+    async fn execute<T>(&self, request: Request<T>, timeout: Duration) -> Result<T> {
+        if !self.connection_state().is_connected() {
+            return Err(DiscordError::ConnectionClosed);
+        }
+
+        let Request {
+            cmd,
+            nonce,
+            message,
+            response,
+        } = request;
+
+        trace!("Executing command: {:?}", cmd);
+
+        let (tx, rx) = oneshot::channel();
+        self.shared.pending.lock().insert(nonce.clone(), tx);
+
+        if let Err(why) = self.send(&message).await {
+            self.shared.pending.lock().remove(&nonce);
+            return Err(why);
+        }
+
+        let message = match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(message)) => message,
+            Ok(Err(_)) => return Err(DiscordError::ConnectionClosed),
+            Err(_) => {
+                // Make sure a late response is dropped, rather than kept around forever
+                self.shared.pending.lock().remove(&nonce);
+                return Err(RecvTimeoutError::Timeout.into());
+            }
+        };
+
+        response.read(&message)
+    }
+
+    async fn send(&self, message: &Message) -> Result<()> {
+        let bytes = message.encode()?;
+        self.writer.lock().await.write_all(&bytes).await?;
+        trace!("-> {:?}", message);
+
+        Ok(())
+    }
+
+    /// Set the users current activity
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn set_activity<F>(&self, f: F) -> Result<Payload<Activity>>
+    where
+        F: FnOnce(Activity) -> Activity,
+    {
+        self.set_activity_with_timeout(f, self.command_timeout)
+            .await
+    }
+
+    /// Set the users current activity, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn set_activity_with_timeout<F>(
+        &self,
+        f: F,
+        timeout: Duration,
+    ) -> Result<Payload<Activity>>
+    where
+        F: FnOnce(Activity) -> Activity,
+    {
+        self.execute(request::set_activity(SetActivityArgs::new(f))?, timeout)
+            .await
+    }
+
+    /// Clear the users current activity
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn clear_activity(&self) -> Result<Payload<Activity>> {
+        self.clear_activity_with_timeout(self.command_timeout).await
+    }
+
+    /// Clear the users current activity, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn clear_activity_with_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Payload<Activity>> {
+        self.execute(request::set_activity(SetActivityArgs::default())?, timeout)
+            .await
+    }
+
+    /// Send an invite to a user to join a game
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn send_activity_join_invite(&self, user_id: u64) -> Result<Payload<Value>> {
+        self.send_activity_join_invite_with_timeout(user_id, self.command_timeout)
+            .await
+    }
+
+    /// Send an invite to a user to join a game, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn send_activity_join_invite_with_timeout(
+        &self,
+        user_id: u64,
+        timeout: Duration,
+    ) -> Result<Payload<Value>> {
+        self.execute(request::send_activity_join_invite(user_id)?, timeout)
+            .await
+    }
+
+    /// Close request to join a game
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn close_activity_request(&self, user_id: u64) -> Result<Payload<Value>> {
+        self.close_activity_request_with_timeout(user_id, self.command_timeout)
+            .await
+    }
+
+    /// Close request to join a game, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn close_activity_request_with_timeout(
+        &self,
+        user_id: u64,
+        timeout: Duration,
+    ) -> Result<Payload<Value>> {
+        self.execute(request::close_activity_request(user_id)?, timeout)
+            .await
+    }
+
+    /// Ask the user to authorize the application for the given `OAuth2` scopes, returning the authorization code
+    ///
+    /// See [`Client::authorize`](crate::Client::authorize).
+    ///
+    /// # Errors
+    /// - The user rejected the prompt
+    /// - See [`DiscordError`] for more info
+    pub async fn authorize<I, S>(&self, scopes: I) -> Result<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.authorize_with_timeout(
+            AuthorizeArgs::new(self.application_id, scopes),
+            AUTHORIZE_TIMEOUT,
+        )
+        .await
+    }
+
+    /// Ask the user to authorize the application, waiting at most `timeout` for them to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if the user did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn authorize_with_timeout(
+        &self,
+        args: AuthorizeArgs,
+        timeout: Duration,
+    ) -> Result<String> {
+        self.execute(request::authorize(args)?, timeout).await
+    }
+
+    /// Authenticate the client with an `OAuth2` access token, giving access to the authorized scopes
+    ///
+    /// # Errors
+    /// - The access token is invalid, or was issued for another application
+    /// - See [`DiscordError`] for more info
+    pub async fn authenticate<S>(&self, access_token: S) -> Result<AuthenticateResponse>
+    where
+        S: Into<String>,
+    {
+        self.authenticate_with_timeout(access_token, self.command_timeout)
+            .await
+    }
+
+    /// Authenticate the client with an `OAuth2` access token, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn authenticate_with_timeout<S>(
+        &self,
+        access_token: S,
+        timeout: Duration,
+    ) -> Result<AuthenticateResponse>
+    where
+        S: Into<String>,
+    {
+        self.execute(request::authenticate(access_token.into())?, timeout)
+            .await
+    }
+
+    /// Get a guild the user is in
+    ///
+    /// Requires the `rpc` scope, see [`AsyncClient::authorize`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn get_guild(&self, guild_id: u64) -> Result<Guild> {
+        self.get_guild_with_timeout(guild_id, self.command_timeout)
+            .await
+    }
+
+    /// Get a guild the user is in, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn get_guild_with_timeout(&self, guild_id: u64, timeout: Duration) -> Result<Guild> {
+        self.execute(request::get_guild(guild_id)?, timeout).await
+    }
+
+    /// Get the guilds the user is in
+    ///
+    /// Requires the `rpc` scope, see [`AsyncClient::authorize`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn get_guilds(&self) -> Result<Vec<PartialGuild>> {
+        self.get_guilds_with_timeout(self.command_timeout).await
+    }
+
+    /// Get the guilds the user is in, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn get_guilds_with_timeout(&self, timeout: Duration) -> Result<Vec<PartialGuild>> {
+        self.execute(request::get_guilds()?, timeout).await
+    }
+
+    /// Get a channel the user can see
+    ///
+    /// Requires the `rpc` scope, see [`AsyncClient::authorize`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn get_channel(&self, channel_id: u64) -> Result<Channel> {
+        self.get_channel_with_timeout(channel_id, self.command_timeout)
+            .await
+    }
+
+    /// Get a channel the user can see, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn get_channel_with_timeout(
+        &self,
+        channel_id: u64,
+        timeout: Duration,
+    ) -> Result<Channel> {
+        self.execute(request::get_channel(channel_id)?, timeout)
+            .await
+    }
+
+    /// Get the channels of a guild the user is in
+    ///
+    /// Requires the `rpc` scope, see [`AsyncClient::authorize`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn get_channels(&self, guild_id: u64) -> Result<Vec<PartialChannel>> {
+        self.get_channels_with_timeout(guild_id, self.command_timeout)
+            .await
+    }
+
+    /// Get the channels of a guild the user is in, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn get_channels_with_timeout(
+        &self,
+        guild_id: u64,
+        timeout: Duration,
+    ) -> Result<Vec<PartialChannel>> {
+        self.execute(request::get_channels(guild_id)?, timeout)
+            .await
+    }
+
+    /// Join a voice channel, or leave the current one if `channel_id` is `None`
+    ///
+    /// Returns the channel that was joined.
+    /// Requires the `rpc` scope, see [`AsyncClient::authorize`].
+    ///
+    /// # Errors
+    /// - The user is already in another voice channel, see [`SelectVoiceChannelArgs::force`]
+    /// - See [`DiscordError`] for more info
+    pub async fn select_voice_channel(&self, channel_id: Option<u64>) -> Result<Option<Channel>> {
+        self.select_voice_channel_with_timeout(
+            SelectVoiceChannelArgs::new(channel_id),
+            self.command_timeout,
+        )
+        .await
+    }
+
+    /// Join or leave a voice channel, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn select_voice_channel_with_timeout(
+        &self,
+        args: SelectVoiceChannelArgs,
+        timeout: Duration,
+    ) -> Result<Option<Channel>> {
+        self.execute(request::select_voice_channel(args)?, timeout)
+            .await
+    }
+
+    /// Get the voice channel the user is in, if any
+    ///
+    /// Requires the `rpc` scope, see [`AsyncClient::authorize`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn get_selected_voice_channel(&self) -> Result<Option<Channel>> {
+        self.get_selected_voice_channel_with_timeout(self.command_timeout)
+            .await
+    }
+
+    /// Get the voice channel the user is in, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn get_selected_voice_channel_with_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<Channel>> {
+        self.execute(request::get_selected_voice_channel()?, timeout)
+            .await
+    }
+
+    /// Join a text channel, or leave the current one if `channel_id` is `None`
+    ///
+    /// Returns the channel that was joined.
+    /// Requires the `rpc` scope, see [`AsyncClient::authorize`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn select_text_channel(&self, channel_id: Option<u64>) -> Result<Option<Channel>> {
+        self.select_text_channel_with_timeout(
+            SelectTextChannelArgs::new(channel_id),
+            self.command_timeout,
+        )
+        .await
+    }
+
+    /// Join or leave a text channel, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn select_text_channel_with_timeout(
+        &self,
+        args: SelectTextChannelArgs,
+        timeout: Duration,
+    ) -> Result<Option<Channel>> {
+        self.execute(request::select_text_channel(args)?, timeout)
+            .await
+    }
+
+    /// Get the voice settings of the user
+    ///
+    /// Requires the `rpc` scope, see [`AsyncClient::authorize`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn get_voice_settings(&self) -> Result<VoiceSettings> {
+        self.get_voice_settings_with_timeout(self.command_timeout)
+            .await
+    }
+
+    /// Get the voice settings of the user, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn get_voice_settings_with_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<VoiceSettings> {
+        self.execute(request::get_voice_settings()?, timeout).await
+    }
+
+    /// Change the voice settings of the user, e.g. to mute or deafen them
+    ///
+    /// Only the settings that are set are changed. Returns the new voice settings.
+    /// Requires the `rpc` scope, see [`AsyncClient::authorize`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn set_voice_settings<F>(&self, f: F) -> Result<VoiceSettings>
+    where
+        F: FnOnce(VoiceSettings) -> VoiceSettings,
+    {
+        self.set_voice_settings_with_timeout(f, self.command_timeout)
+            .await
+    }
+
+    /// Change the voice settings of the user, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn set_voice_settings_with_timeout<F>(
+        &self,
+        f: F,
+        timeout: Duration,
+    ) -> Result<VoiceSettings>
+    where
+        F: FnOnce(VoiceSettings) -> VoiceSettings,
+    {
+        self.execute(
+            request::set_voice_settings(f(VoiceSettings::new()))?,
+            timeout,
+        )
+        .await
+    }
+
+    /// Change how the user hears another user, e.g. their volume
+    ///
+    /// Returns the new settings for that user.
+    /// Requires the `rpc` scope, see [`AsyncClient::authorize`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn set_user_voice_settings<F>(&self, user_id: u64, f: F) -> Result<UserVoiceSettings>
+    where
+        F: FnOnce(UserVoiceSettings) -> UserVoiceSettings,
+    {
+        self.set_user_voice_settings_with_timeout(user_id, f, self.command_timeout)
+            .await
+    }
+
+    /// Change how the user hears another user, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn set_user_voice_settings_with_timeout<F>(
+        &self,
+        user_id: u64,
+        f: F,
+        timeout: Duration,
+    ) -> Result<UserVoiceSettings>
+    where
+        F: FnOnce(UserVoiceSettings) -> UserVoiceSettings,
+    {
+        self.execute(
+            request::set_user_voice_settings(user_id, f(UserVoiceSettings::new()))?,
+            timeout,
+        )
+        .await
+    }
+
+    /// Subscribe to a given event
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn subscribe<F>(&self, evt: Event, f: F) -> Result<Payload<Subscription>>
+    where
+        F: FnOnce(SubscriptionArgs) -> SubscriptionArgs,
+    {
+        self.subscribe_with_timeout(evt, f, self.command_timeout)
+            .await
+    }
+
+    /// Subscribe to a given event, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn subscribe_with_timeout<F>(
+        &self,
+        evt: Event,
+        f: F,
+        timeout: Duration,
+    ) -> Result<Payload<Subscription>>
+    where
+        F: FnOnce(SubscriptionArgs) -> SubscriptionArgs,
+    {
+        self.execute(
+            request::subscribe(evt, f(SubscriptionArgs::new()))?,
+            timeout,
+        )
+        .await
+    }
+
+    /// Unsubscribe from a given event
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub async fn unsubscribe<F>(&self, evt: Event, f: F) -> Result<Payload<Subscription>>
+    where
+        F: FnOnce(SubscriptionArgs) -> SubscriptionArgs,
+    {
+        self.unsubscribe_with_timeout(evt, f, self.command_timeout)
+            .await
+    }
+
+    /// Unsubscribe from a given event, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub async fn unsubscribe_with_timeout<F>(
+        &self,
+        evt: Event,
+        f: F,
+        timeout: Duration,
+    ) -> Result<Payload<Subscription>>
+    where
+        F: FnOnce(SubscriptionArgs) -> SubscriptionArgs,
+    {
+        self.execute(
+            request::unsubscribe(evt, f(SubscriptionArgs::new()))?,
+            timeout,
+        )
+        .await
+    }
+
+    /// Listens for a given event, and returns a handle that unregisters the listener when it is dropped
+    ///
+    /// The handler is called on the task reading from Discord, so it must not block.
+    pub fn on_event<F>(&self, event: Event, handler: F) -> EventCallbackHandle
+    where
+        F: Fn(EventContext) + 'static + Send + Sync,
+    {
+        self.shared.event_handler_registry.register(event, handler)
+    }
+
+    #[must_use]
+    /// A stream of every event received from now on, see [`Client::events`](crate::Client::events)
+    ///
+    /// Implements `futures_core::Stream`, and ends once the connection is lost or the client is shut down.
+    pub fn events(&self) -> Events {
+        self.events_with_capacity(DEFAULT_EVENT_CAPACITY, OverflowPolicy::default())
+    }
+
+    #[must_use]
+    /// A stream of every event received from now on, buffering at most `capacity` events
+    pub fn events_with_capacity(&self, capacity: usize, policy: OverflowPolicy) -> Events {
+        self.shared.event_handler_registry.stream(capacity, policy)
+    }
+
+    /// Listens for any event whose data could not be parsed, or that the crate does not know,
+    /// with the name Discord sent the event with, e.g. `ACTIVITY_JOIN`, and its raw JSON data
+    ///
+    /// The typed handlers, such as `on_ready`, are not called for these events.
+    pub fn on_raw_event<F>(&self, handler: F) -> EventCallbackHandle
+    where
+        F: Fn(&str, Value) + 'static + Send + Sync,
+    {
+        self.shared
+            .event_handler_registry
+            .register_raw(move |ctx: EventContext| {
+                if let EventData::Unknown(data) = ctx.event {
+                    handler(&ctx.kind.name(), data);
+                }
+            })
+    }
+
+    event_handler_function!(on_error, Event::Error, Error(ErrorEvent));
+
+    event_handler_function!(
+        on_activity_join,
+        Event::ActivityJoin,
+        ActivityJoin(ActivityJoinEvent)
+    );
+
+    event_handler_function!(
+        on_activity_join_request,
+        Event::ActivityJoinRequest,
+        ActivityJoinRequest(ActivityJoinRequestEvent)
+    );
+
+    event_handler_function!(
+        on_activity_spectate,
+        Event::ActivitySpectate,
+        ActivitySpectate(ActivitySpectateEvent)
+    );
+
+    event_handler_function!(
+        on_activity_invite,
+        Event::ActivityInvite,
+        ActivityInvite(ActivityInviteEvent)
+    );
+
+    event_handler_function!(
+        on_voice_state_create,
+        Event::VoiceStateCreate,
+        VoiceState(VoiceStateEvent)
+    );
+
+    event_handler_function!(
+        on_voice_state_update,
+        Event::VoiceStateUpdate,
+        VoiceState(VoiceStateEvent)
+    );
+
+    event_handler_function!(
+        on_voice_state_delete,
+        Event::VoiceStateDelete,
+        VoiceState(VoiceStateEvent)
+    );
+
+    event_handler_function!(
+        on_speaking_start,
+        Event::SpeakingStart,
+        Speaking(SpeakingEvent)
+    );
+
+    event_handler_function!(
+        on_speaking_stop,
+        Event::SpeakingStop,
+        Speaking(SpeakingEvent)
+    );
+
+    event_handler_function!(
+        on_voice_connection_status,
+        Event::VoiceConnectionStatus,
+        VoiceConnectionStatus(VoiceConnectionStatusEvent)
+    );
+
+    event_handler_function!(
+        on_message_create,
+        Event::MessageCreate,
+        Message(MessageEvent)
+    );
+
+    event_handler_function!(
+        on_message_update,
+        Event::MessageUpdate,
+        Message(MessageEvent)
+    );
+
+    event_handler_function!(
+        on_message_delete,
+        Event::MessageDelete,
+        Message(MessageEvent)
+    );
+
+    event_handler_function!(
+        on_guild_status,
+        Event::GuildStatus,
+        GuildStatus(GuildStatusEvent)
+    );
+
+    event_handler_function!(
+        on_guild_create,
+        Event::GuildCreate,
+        GuildCreate(GuildCreateEvent)
+    );
+
+    event_handler_function!(
+        on_channel_create,
+        Event::ChannelCreate,
+        ChannelCreate(ChannelCreateEvent)
+    );
+
+    event_handler_function!(
+        on_notification_create,
+        Event::NotificationCreate,
+        NotificationCreate(NotificationCreateEvent)
+    );
+
+    event_handler_function!(
+        on_disconnected,
+        Event::Disconnected,
+        ConnectionState(ConnectionStateEvent)
+    );
+
+    event_handler_function!(
+        on_connection_state_changed,
+        Event::ConnectionStateChanged,
+        ConnectionState(ConnectionStateEvent)
+    );
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::models::{commands::AuthenticateArgs, Command};
+    use serde_json::json;
+    use std::{env, fs, path::PathBuf};
+    use tokio::net::{UnixListener, UnixStream};
+
+    fn socket_path() -> PathBuf {
+        let dir = env::temp_dir().join(format!("discord-presence-{}", crate::utils::nonce()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("discord-ipc-0")
+    }
+
+    async fn send_frame(stream: &mut UnixStream, payload: &Value) {
+        let message = Message::new(OpCode::Frame, payload).unwrap();
+        stream.write_all(&message.encode().unwrap()).await.unwrap();
+    }
+
+    /// Accepts a client and completes the handshake, like Discord would
+    async fn accept(listener: &UnixListener) -> (UnixStream, BytesMut) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buffer = BytesMut::new();
+
+        let handshake = recv(&mut stream, &mut buffer).await.unwrap();
+        assert_eq!(handshake.opcode, OpCode::Handshake);
+
+        send_frame(
+            &mut stream,
+            &json!({ "cmd": "DISPATCH", "evt": "READY", "data": { "v": 1, "user": { "id": "1" } } }),
+        )
+        .await;
+
+        (stream, buffer)
+    }
+
+    #[tokio::test]
+    async fn executes_commands_and_dispatches_events() {
+        let path = socket_path();
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let discord = async {
+            let (mut stream, mut buffer) = accept(&listener).await;
+
+            let request = recv(&mut stream, &mut buffer).await.unwrap();
+            let request: Payload<Value> = serde_json::from_str(&request.payload).unwrap();
+            assert_eq!(request.cmd, Command::SetActivity);
+
+            // Events can arrive before the response
+            send_frame(
+                &mut stream,
+                &json!({ "cmd": "DISPATCH", "evt": "ACTIVITY_JOIN", "data": { "secret": "s" } }),
+            )
+            .await;
+            send_frame(
+                &mut stream,
+                &json!({ "cmd": "SET_ACTIVITY", "data": { "state": "rusting", "buttons": [] }, "nonce": request.nonce }),
+            )
+            .await;
+
+            stream
+        };
+        let client = async {
+            let client = ClientBuilder::new(1)
+                .ipc_path(&path)
+                .connect_async()
+                .await
+                .unwrap();
+            let (tx, rx) = crossbeam_channel::unbounded();
+            let _join = client.on_activity_join(move |join| tx.send(join).unwrap());
+
+            let activity = client.set_activity(|a| a.state("rusting")).await.unwrap();
+            (client, activity, rx)
+        };
+        let (stream, (client, activity, rx)) = tokio::join!(discord, client);
+
+        assert_eq!(activity.data.unwrap().state.as_deref(), Some("rusting"));
+        assert_eq!(rx.try_recv().unwrap().secret.as_deref(), Some("s"));
+        assert_eq!(client.ipc_slot(), None);
+        assert_eq!(client.current_user().unwrap().id.as_deref(), Some("1"));
+        assert_eq!(client.connection_state(), ConnectionState::Connected);
+
+        drop(stream);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        // The reader task notices the connection is gone
+        let (tx, rx) = oneshot::channel();
+        let tx = Mutex::new(Some(tx));
+        let _disconnected = client.on_disconnected(move |_| {
+            if let Some(tx) = tx.lock().take() {
+                tx.send(()).unwrap();
+            }
+        });
+        rx.await.unwrap();
+
+        assert!(matches!(
+            client.clear_activity().await,
+            Err(DiscordError::ConnectionClosed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn authorizes_and_authenticates() {
+        let path = socket_path();
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let discord = async {
+            let (mut stream, mut buffer) = accept(&listener).await;
+
+            let authorize = recv(&mut stream, &mut buffer).await.unwrap();
+            let authorize: Payload<AuthorizeArgs> =
+                serde_json::from_str(&authorize.payload).unwrap();
+            assert_eq!(authorize.cmd, Command::Authorize);
+            assert_eq!(
+                authorize.args,
+                Some(AuthorizeArgs::new(7, ["rpc", "identify"]))
+            );
+            send_frame(
+                &mut stream,
+                &json!({ "cmd": "AUTHORIZE", "data": { "code": "abc" }, "nonce": authorize.nonce }),
+            )
+            .await;
+
+            let authenticate = recv(&mut stream, &mut buffer).await.unwrap();
+            let authenticate: Payload<AuthenticateArgs> =
+                serde_json::from_str(&authenticate.payload).unwrap();
+            assert_eq!(authenticate.args, Some(AuthenticateArgs::new("token-abc")));
+            send_frame(
+                &mut stream,
+                &json!({
+                    "cmd": "AUTHENTICATE",
+                    "data": {
+                        "user": { "id": "1", "username": "rustacean" },
+                        "scopes": ["rpc", "identify"],
+                        "expires": "2024-01-01T00:00:00.000Z",
+                        "application": { "id": "7", "name": "Test", "description": "", "icon": null, "rpc_origins": [] }
+                    },
+                    "nonce": authenticate.nonce
+                }),
+            )
+            .await;
+
+            stream
+        };
+        let client = async {
+            let client = ClientBuilder::new(7)
+                .ipc_path(&path)
+                .connect_async()
+                .await
+                .unwrap();
+
+            let code = client.authorize(["rpc", "identify"]).await.unwrap();
+            client.authenticate(format!("token-{code}")).await.unwrap()
+        };
+        let (_stream, auth) = tokio::join!(discord, client);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(auth.user.username.as_deref(), Some("rustacean"));
+        assert_eq!(auth.scopes, vec!["rpc", "identify"]);
+        assert_eq!(auth.application.name, "Test");
+    }
+
+    #[tokio::test]
+    async fn times_out_without_response() {
+        let path = socket_path();
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let (client, (_stream, _)) = tokio::join!(
+            ClientBuilder::new(1)
+                .ipc_path(&path)
+                .command_timeout(Duration::from_millis(10))
+                .connect_async(),
+            accept(&listener)
+        );
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+        let client = client.unwrap();
+
+        assert!(matches!(
+            client.clear_activity().await,
+            Err(DiscordError::TimeoutError(_))
+        ));
+        assert!(client.shared.pending.lock().is_empty());
+
+        client.shutdown().await.unwrap();
+
+        // Accepted, but the handshake is never answered
+        let path = socket_path();
+        let listener = UnixListener::bind(&path).unwrap();
+        let (client, accepted) = tokio::join!(
+            ClientBuilder::new(1)
+                .ipc_path(&path)
+                .command_timeout(Duration::from_millis(10))
+                .connect_async(),
+            listener.accept()
+        );
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert!(accepted.is_ok());
+        assert!(matches!(client, Err(DiscordError::TimeoutError(_))));
+    }
+}
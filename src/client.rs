@@ -1,43 +1,35 @@
 use std::{
-    sync::{atomic::Ordering, Arc},
+    path::{Path, PathBuf},
+    sync::Arc,
     thread::{JoinHandle, Thread},
+    time::{Duration, Instant},
 };
 
 use crate::{
-    connection::Manager as ConnectionManager,
+    connection::{
+        ConnectionConfig, ConnectionState, Connector, Manager as ConnectionManager,
+        ReconnectPolicy, Replay, Transcript, Transport,
+    },
+    dispatch::DispatchMode,
     event_handler::{Context as EventContext, EventCallbackHandle, HandlerRegistry},
+    event_stream::{Events, OverflowPolicy, DEFAULT_EVENT_CAPACITY},
     models::{
-        commands::{Subscription, SubscriptionArgs},
-        message::Message,
+        commands::{AuthenticateResponse, AuthorizeArgs, Subscription, SubscriptionArgs},
+        guild::{Channel, Guild, PartialChannel, PartialGuild},
         payload::Payload,
-        rich_presence::{
-            Activity, CloseActivityRequestArgs, SendActivityJoinInviteArgs, SetActivityArgs,
-        },
-        Command, Event, OpCode,
+        rich_presence::{Activity, SetActivityArgs},
+        voice::{SelectTextChannelArgs, SelectVoiceChannelArgs, UserVoiceSettings, VoiceSettings},
+        ActivityInviteEvent, ActivityJoinEvent, ActivityJoinRequestEvent, ActivitySpectateEvent,
+        ChannelCreateEvent, ConnectionStateEvent, ErrorEvent, Event, EventData, GuildCreateEvent,
+        GuildStatusEvent, MessageEvent, NotificationCreateEvent, PartialUser, ReadyEvent,
+        ReconnectFailedEvent, SpeakingEvent, VoiceConnectionStatusEvent, VoiceStateEvent,
     },
+    request::{self, Request},
     DiscordError, Result,
 };
-use crossbeam_channel::Sender;
-use serde::{de::DeserializeOwned, Serialize};
+use crossbeam_channel::{RecvTimeoutError, Sender};
 use serde_json::Value;
 
-macro_rules! event_handler_function {
-    ( $( $name:ident, $event:expr ),* ) => {
-        event_handler_function!{@gen $([ $name, $event])*}
-    };
-
-    (@gen $( [ $name:ident, $event:expr ] ), *) => {
-        $(
-            #[doc = concat!("Listens for the `", stringify!($event), "` event")]
-            pub fn $name<F>(&self, handler: F) -> EventCallbackHandle
-                where F: Fn(EventContext) + 'static + Send + Sync
-            {
-                self.on_event($event, handler)
-            }
-        )*
-    }
-}
-
 /// Wrapper around the [`JoinHandle`] returned by [`Client::start`]
 #[allow(clippy::module_name_repetitions)]
 pub struct ClientThread(JoinHandle<()>, Sender<()>);
@@ -85,12 +77,251 @@ impl ClientThread {
     }
 }
 
+// Discord rate limit timeout is 15 seconds, so 16 should account for that
+const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(16);
+
+// Authorizing waits for the user to accept the prompt in Discord, which takes a lot longer.
+// `Duration::from_mins` is too recent to use here
+#[allow(clippy::duration_suboptimal_units)]
+pub(crate) const AUTHORIZE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Builder for a [`Client`] with non-default options
+///
+/// # Examples
+///
+/// ```no_run
+/// # use discord_presence::Client;
+/// let mut drpc = Client::builder(1003450375732482138)
+///     .search_dir("/run/user/1000/my-sandbox")
+///     .build();
+///
+/// drpc.start();
+/// ```
+#[derive(Debug, Clone)]
+#[allow(clippy::module_name_repetitions)]
+pub struct ClientBuilder {
+    pub(crate) client_id: u64,
+    pub(crate) connection: ConnectionConfig,
+    reconnect_policy: ReconnectPolicy,
+    restore_session: bool,
+    pub(crate) command_timeout: Duration,
+    dispatch_mode: DispatchMode,
+    replay_ready: bool,
+}
+
+impl ClientBuilder {
+    #[must_use]
+    /// Creates a new `ClientBuilder` for the given application
+    pub fn new(client_id: u64) -> Self {
+        Self {
+            client_id,
+            connection: ConnectionConfig::default(),
+            reconnect_policy: ReconnectPolicy::default(),
+            restore_session: true,
+            command_timeout: DEFAULT_COMMAND_TIMEOUT,
+            dispatch_mode: DispatchMode::default(),
+            replay_ready: false,
+        }
+    }
+
+    #[must_use]
+    /// Adds a directory to search for the `discord-ipc-N` sockets in
+    ///
+    /// Extra directories are searched before the default ones, in the order they were added.
+    ///
+    /// On Unix, the default directories are searched in the following order,
+    /// relative to `$XDG_RUNTIME_DIR` (or `$TMPDIR`, falling back to `/tmp`):
+    /// - The directory itself
+    /// - `app/com.discordapp.Discord` (Flatpak)
+    /// - `app/com.discordapp.DiscordCanary` (Flatpak Canary)
+    /// - `app/com.discordapp.DiscordPTB` (Flatpak PTB)
+    /// - `snap.discord` (Snap)
+    /// - `snap.discord-canary` (Snap Canary)
+    /// - `app/dev.vencord.Vesktop` (Vesktop Flatpak)
+    /// - `.flatpak/dev.vencord.Vesktop/xdg-run` (Vesktop Flatpak)
+    pub fn search_dir<P>(mut self, dir: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.connection.search_dirs.push(dir.into());
+        self
+    }
+
+    #[must_use]
+    /// Connects to exactly the IPC socket at the given path, rather than searching for it
+    ///
+    /// Useful for sandboxes, or for testing against a local server.
+    /// If not set, the `DISCORD_IPC_PATH` environment variable is used instead, if present.
+    pub fn ipc_path<P>(mut self, path: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.connection.ipc_path = Some(path.into());
+        self
+    }
+
+    #[must_use]
+    /// Connects with a custom [`Transport`] rather than the IPC socket, e.g. a proxy or a bridge to another machine
+    ///
+    /// `connect` is called on every connection attempt, including reconnects,
+    /// and any [`Read`](std::io::Read) + [`Write`](std::io::Write) stream can be wrapped in a [`StreamTransport`](crate::StreamTransport).
+    /// The IPC path and search directories are ignored when this is set.
+    pub fn connector<F, T>(mut self, connect: F) -> Self
+    where
+        F: Fn() -> Result<T> + Send + Sync + 'static,
+        T: Transport + 'static,
+    {
+        self.connection.connector = Some(Connector::new(connect));
+        self
+    }
+
+    #[cfg(feature = "websocket")]
+    #[must_use]
+    /// Connects to Discord's local WebSocket RPC server, on the first of ports 6463 to 6472 it listens on, rather than the IPC socket
+    ///
+    /// Useful where localhost is reachable but the IPC socket is not, e.g. in a container.
+    /// `origin` is sent as the `Origin` header, and has to be allowed in the RPC settings of the application.
+    /// [`Client::ipc_slot`] is the index of the port, from 0 for 6463.
+    /// Replaces any [`connector`](ClientBuilder::connector), and is only available with the `websocket` feature.
+    pub fn websocket<S>(self, origin: S) -> Self
+    where
+        S: Into<String>,
+    {
+        let client_id = self.client_id;
+        let origin = origin.into();
+
+        self.connector(move || crate::WebSocketTransport::connect(client_id, &origin))
+    }
+
+    #[must_use]
+    /// Records every message exchanged with Discord to a JSON-lines file, e.g. to attach to a bug report
+    ///
+    /// Each line holds the `direction` (`sent` or `received`), `opcode`, `payload` and `timestamp` of a message.
+    /// Authorization codes and tokens in payloads are replaced with `[redacted]`, so the file can be shared.
+    /// The file is created if needed, and appended to otherwise.
+    /// See [`ClientBuilder::replay_transcript`] to play it back.
+    pub fn record_transcript<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.connection.record = Some(Transcript::new(path.as_ref()));
+        self
+    }
+
+    #[must_use]
+    /// Plays back a transcript recorded with [`ClientBuilder::record_transcript`], rather than connecting to Discord
+    ///
+    /// Each recorded message from Discord is received as soon as the messages recorded before it were sent,
+    /// with responses matched to the commands sent now, so that a bug report can become a regression test.
+    /// A command that does not match the one recorded next fails with [`DiscordError::TranscriptMismatch`].
+    pub fn replay_transcript<P>(mut self, path: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.connection.replay = Some(Replay::new(path.as_ref()));
+        self
+    }
+
+    #[must_use]
+    /// Sets whether [`Client::shutdown`] clears the activity before closing the connection
+    ///
+    /// Disabled by default, in which case Discord clears it on its own once it notices the connection closed,
+    /// which can take a while.
+    pub fn clear_activity_on_shutdown(mut self, clear: bool) -> Self {
+        self.connection.clear_activity_on_shutdown = clear;
+        self
+    }
+
+    #[must_use]
+    /// Sets how long [`Client::shutdown`] waits for Discord to clear the activity and acknowledge the connection closing
+    ///
+    /// Defaults to 500 milliseconds. The connection is closed regardless once it passes.
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.connection.shutdown_timeout = Some(timeout);
+        self
+    }
+
+    #[must_use]
+    /// Sets how the client retries connecting to Discord
+    ///
+    /// Defaults to [`ReconnectPolicy::Exponential`], from 1 second up to 30 seconds, retrying forever.
+    /// When the policy gives up, [`Event::ReconnectFailed`] is fired and the client stops.
+    pub fn reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    #[must_use]
+    /// Sets whether the last activity and subscriptions are re-applied after reconnecting
+    ///
+    /// Enabled by default, so that the presence survives Discord restarting.
+    pub fn restore_session(mut self, restore: bool) -> Self {
+        self.restore_session = restore;
+        self
+    }
+
+    #[must_use]
+    /// Sets how long commands such as [`Client::set_activity`] wait for Discord to respond
+    ///
+    /// Defaults to 16 seconds. Each command also has a `_with_timeout` variant to override this per call.
+    pub fn command_timeout(mut self, timeout: Duration) -> Self {
+        self.command_timeout = timeout;
+        self
+    }
+
+    #[must_use]
+    /// Sets how event handlers are called
+    ///
+    /// Defaults to [`DispatchMode::Dedicated`], calling handlers in order on a single dispatcher thread.
+    pub fn dispatch_mode(mut self, mode: DispatchMode) -> Self {
+        self.dispatch_mode = mode;
+        self
+    }
+
+    #[must_use]
+    /// Sets whether the last [`ReadyEvent`] is delivered to handlers registered after the handshake
+    ///
+    /// When enabled, [`Client::on_ready`] handlers are called right away if the client is already ready,
+    /// and [`Client::block_until_event`] returns right away for [`Event::Ready`].
+    /// Disabled by default. Either way, the data is available from [`Client::ready_data`].
+    pub fn replay_ready(mut self, replay: bool) -> Self {
+        self.replay_ready = replay;
+        self
+    }
+
+    #[must_use]
+    /// Creates the [`Client`]
+    pub fn build(self) -> Client {
+        let event_handler_registry =
+            Arc::new(HandlerRegistry::with_dispatch_mode(self.dispatch_mode));
+        let connection_manager = ConnectionManager::new(
+            self.client_id,
+            self.connection,
+            self.reconnect_policy,
+            self.restore_session,
+            event_handler_registry.clone(),
+        );
+
+        Client {
+            application_id: self.client_id,
+            connection_manager,
+            event_handler_registry,
+            thread: None,
+            command_timeout: self.command_timeout,
+            replay_ready: self.replay_ready,
+        }
+    }
+}
+
 #[derive(Clone)]
 /// The Discord client
 pub struct Client {
+    application_id: u64,
     connection_manager: ConnectionManager,
     event_handler_registry: Arc<HandlerRegistry>,
     thread: Option<Arc<ClientThread>>,
+    command_timeout: Duration,
+    replay_ready: bool,
 }
 
 #[cfg(feature = "bevy")]
@@ -100,14 +331,13 @@ impl Client {
     /// Creates a new `Client`
     #[must_use]
     pub fn new(client_id: u64) -> Self {
-        let event_handler_registry = Arc::new(HandlerRegistry::new());
-        let connection_manager = ConnectionManager::new(client_id, event_handler_registry.clone());
+        ClientBuilder::new(client_id).build()
+    }
 
-        Self {
-            connection_manager,
-            event_handler_registry,
-            thread: None,
-        }
+    /// Creates a [`ClientBuilder`], to configure the `Client` before creating it
+    #[must_use]
+    pub fn builder(client_id: u64) -> ClientBuilder {
+        ClientBuilder::new(client_id)
     }
 
     // TODO: Add examples
@@ -117,6 +347,8 @@ impl Client {
     ///
     /// This must be called before all and any actions such as `set_activity`
     pub fn start(&mut self) {
+        self.event_handler_registry.start_dispatcher();
+
         // Shutdown notify channel
         let (tx, rx) = crossbeam_channel::bounded::<()>(1);
 
@@ -127,6 +359,9 @@ impl Client {
 
     /// Shutdown the client and its thread
     ///
+    /// Commands still queued are sent, and the activity is cleared if enabled with [`ClientBuilder::clear_activity_on_shutdown`].
+    /// Then the connection is closed the way Discord expects, waiting at most [`ClientBuilder::shutdown_timeout`] for it.
+    ///
     /// # Errors
     /// - The internal connection thread ran into an error
     /// - The client was not started, or has already been shutdown
@@ -134,7 +369,7 @@ impl Client {
         if let Some(thread) = self.thread.as_ref() {
             thread.1.send(())?;
 
-            crate::READY.store(false, Ordering::Relaxed);
+            self.connection_manager.set_ready(false);
 
             self.block_on()
         } else {
@@ -173,44 +408,103 @@ impl Client {
 
     #[must_use]
     /// Check if the client is ready
-    pub fn is_ready() -> bool {
-        crate::READY.load(Ordering::Relaxed)
+    pub fn is_ready(&self) -> bool {
+        self.connection_manager.is_ready()
     }
 
-    fn execute<A, E>(&mut self, cmd: Command, args: A, evt: Option<Event>) -> Result<Payload<E>>
-    where
-        A: Serialize + Send + Sync,
-        E: Serialize + DeserializeOwned + Send + Sync,
-    {
-        if !crate::READY.load(Ordering::Relaxed) {
+    #[must_use]
+    /// The current state of the connection to Discord
+    ///
+    /// Listen to [`Client::on_connection_state_changed`] to be notified of changes.
+    pub fn connection_state(&self) -> ConnectionState {
+        self.connection_manager.connection_state()
+    }
+
+    #[must_use]
+    /// The IPC slot (`discord-ipc-N`) the client is currently connected on, if any
+    ///
+    /// Discord listens on the first free slot between `0` and `9`,
+    /// so this is usually `0`, unless another instance (e.g. PTB or Canary) is also running.
+    ///
+    /// Always `None` when connected through [`ClientBuilder::ipc_path`].
+    pub fn ipc_slot(&self) -> Option<u8> {
+        self.connection_manager.slot()
+    }
+
+    #[must_use]
+    /// The data Discord sent when the client last connected, if it has connected yet
+    ///
+    /// Kept even after the connection is lost.
+    pub fn ready_data(&self) -> Option<ReadyEvent> {
+        self.connection_manager.ready_data()
+    }
+
+    #[must_use]
+    /// The user logged into Discord, if the client has connected yet
+    pub fn current_user(&self) -> Option<PartialUser> {
+        self.ready_data().and_then(|ready| ready.user)
+    }
+
+    fn execute<T>(&mut self, request: Request<T>, timeout: Duration) -> Result<T> {
+        if !self.is_ready() {
             return Err(DiscordError::NotStarted);
         }
 
+        let Request {
+            cmd,
+            nonce,
+            message,
+            response,
+        } = request;
+
         trace!("Executing command: {:?}", cmd);
 
-        let message = Message::new(
-            OpCode::Frame,
-            Payload::with_nonce(cmd, Some(args), None, evt),
-        );
-        self.connection_manager.send(message?)?;
-        let Message { payload, .. } = self.connection_manager.recv()?;
-        let response: Payload<E> = serde_json::from_str(&payload)?;
+        let rx = self.connection_manager.request(&nonce, message)?;
+        let message = match rx.recv_timeout(timeout) {
+            Ok(Ok(message)) => message,
+            Ok(Err(why)) => return Err(why),
+            Err(RecvTimeoutError::Timeout) => {
+                // Make sure a late response is dropped, rather than kept around forever
+                self.connection_manager.cancel(&nonce);
+                return Err(RecvTimeoutError::Timeout.into());
+            }
+            Err(RecvTimeoutError::Disconnected) => return Err(DiscordError::ConnectionClosed),
+        };
 
-        match response.evt {
-            Some(Event::Error) => Err(DiscordError::SubscriptionFailed),
-            _ => Ok(response),
-        }
+        response.read(&message)
     }
 
     /// Set the users current activity
     ///
+    /// The activity is set again automatically after reconnecting, see [`ClientBuilder::restore_session`].
+    ///
     /// # Errors
     /// - See [`DiscordError`] for more info
     pub fn set_activity<F>(&mut self, f: F) -> Result<Payload<Activity>>
     where
         F: FnOnce(Activity) -> Activity,
     {
-        self.execute(Command::SetActivity, SetActivityArgs::new(f), None)
+        self.set_activity_with_timeout(f, self.command_timeout)
+    }
+
+    /// Set the users current activity, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn set_activity_with_timeout<F>(
+        &mut self,
+        f: F,
+        timeout: Duration,
+    ) -> Result<Payload<Activity>>
+    where
+        F: FnOnce(Activity) -> Activity,
+    {
+        let args = SetActivityArgs::new(f);
+        let response = self.execute(request::set_activity(args.clone())?, timeout)?;
+        self.connection_manager.session().set_activity(args);
+
+        Ok(response)
     }
 
     /// Clear the users current activity
@@ -218,7 +512,19 @@ impl Client {
     /// # Errors
     /// - See [`DiscordError`] for more info
     pub fn clear_activity(&mut self) -> Result<Payload<Activity>> {
-        self.execute(Command::SetActivity, SetActivityArgs::default(), None)
+        self.clear_activity_with_timeout(self.command_timeout)
+    }
+
+    /// Clear the users current activity, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn clear_activity_with_timeout(&mut self, timeout: Duration) -> Result<Payload<Activity>> {
+        let response = self.execute(request::set_activity(SetActivityArgs::default())?, timeout)?;
+        self.connection_manager.session().clear_activity();
+
+        Ok(response)
     }
 
     // NOTE: Not sure what the actual response values of
@@ -229,11 +535,20 @@ impl Client {
     /// # Errors
     /// - See [`DiscordError`] for more info
     pub fn send_activity_join_invite(&mut self, user_id: u64) -> Result<Payload<Value>> {
-        self.execute(
-            Command::SendActivityJoinInvite,
-            SendActivityJoinInviteArgs::new(user_id),
-            None,
-        )
+        self.send_activity_join_invite_with_timeout(user_id, self.command_timeout)
+    }
+
+    /// Send an invite to a user to join a game, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn send_activity_join_invite_with_timeout(
+        &mut self,
+        user_id: u64,
+        timeout: Duration,
+    ) -> Result<Payload<Value>> {
+        self.execute(request::send_activity_join_invite(user_id)?, timeout)
     }
 
     /// Close request to join a game
@@ -241,22 +556,410 @@ impl Client {
     /// # Errors
     /// - See [`DiscordError`] for more info
     pub fn close_activity_request(&mut self, user_id: u64) -> Result<Payload<Value>> {
+        self.close_activity_request_with_timeout(user_id, self.command_timeout)
+    }
+
+    /// Close request to join a game, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn close_activity_request_with_timeout(
+        &mut self,
+        user_id: u64,
+        timeout: Duration,
+    ) -> Result<Payload<Value>> {
+        self.execute(request::close_activity_request(user_id)?, timeout)
+    }
+
+    /// Ask the user to authorize the application for the given `OAuth2` scopes, returning the authorization code
+    ///
+    /// Discord shows the user a prompt, so this waits up to 5 minutes for them to respond.
+    /// The code then has to be exchanged for an access token, e.g. by your backend,
+    /// which is passed to [`Client::authenticate`].
+    /// See [`Client::authorize_and_authenticate`] to do both in one go.
+    ///
+    /// # Errors
+    /// - The user rejected the prompt
+    /// - See [`DiscordError`] for more info
+    pub fn authorize<I, S>(&mut self, scopes: I) -> Result<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.authorize_with_timeout(
+            AuthorizeArgs::new(self.application_id, scopes),
+            AUTHORIZE_TIMEOUT,
+        )
+    }
+
+    /// Ask the user to authorize the application, waiting at most `timeout` for them to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if the user did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn authorize_with_timeout(
+        &mut self,
+        args: AuthorizeArgs,
+        timeout: Duration,
+    ) -> Result<String> {
+        self.execute(request::authorize(args)?, timeout)
+    }
+
+    /// Authenticate the client with an `OAuth2` access token, giving access to the authorized scopes
+    ///
+    /// # Errors
+    /// - The access token is invalid, or was issued for another application
+    /// - See [`DiscordError`] for more info
+    pub fn authenticate<S>(&mut self, access_token: S) -> Result<AuthenticateResponse>
+    where
+        S: Into<String>,
+    {
+        self.authenticate_with_timeout(access_token, self.command_timeout)
+    }
+
+    /// Authenticate the client with an `OAuth2` access token, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn authenticate_with_timeout<S>(
+        &mut self,
+        access_token: S,
+        timeout: Duration,
+    ) -> Result<AuthenticateResponse>
+    where
+        S: Into<String>,
+    {
+        let access_token = access_token.into();
+        let response = self.execute(request::authenticate(access_token.clone())?, timeout)?;
+
+        // Authenticated again after reconnecting, before restoring the subscriptions
+        self.connection_manager.session().authenticate(access_token);
+
+        Ok(response)
+    }
+
+    /// Authorize the application, exchange the code for an access token with `exchange`, and authenticate with it
+    ///
+    /// The exchange needs the application's client secret, so it usually happens on a backend,
+    /// by posting the code to Discord's `/oauth2/token` endpoint.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use discord_presence::Client;
+    /// # fn exchange_on_backend(code: &str) -> anyhow::Result<String> { unimplemented!() }
+    /// let mut drpc = Client::new(1003450375732482138);
+    /// drpc.start();
+    ///
+    /// let auth = drpc
+    ///     .authorize_and_authenticate(["rpc", "identify"], |code| exchange_on_backend(code))
+    ///     .unwrap();
+    ///
+    /// println!("Authenticated as {:?}", auth.user.username);
+    /// ```
+    ///
+    /// # Errors
+    /// - [`DiscordError::TokenExchange`] if `exchange` failed
+    /// - See [`Client::authorize`] and [`Client::authenticate`]
+    pub fn authorize_and_authenticate<I, S, F, E>(
+        &mut self,
+        scopes: I,
+        exchange: F,
+    ) -> Result<AuthenticateResponse>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+        F: FnOnce(&str) -> std::result::Result<String, E>,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        let code = self.authorize(scopes)?;
+        let access_token =
+            exchange(&code).map_err(|why| DiscordError::TokenExchange(why.into()))?;
+
+        self.authenticate(access_token)
+    }
+
+    /// Get a guild the user is in
+    ///
+    /// Requires the `rpc` scope, see [`Client::authorize_and_authenticate`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub fn get_guild(&mut self, guild_id: u64) -> Result<Guild> {
+        self.get_guild_with_timeout(guild_id, self.command_timeout)
+    }
+
+    /// Get a guild the user is in, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn get_guild_with_timeout(&mut self, guild_id: u64, timeout: Duration) -> Result<Guild> {
+        self.execute(request::get_guild(guild_id)?, timeout)
+    }
+
+    /// Get the guilds the user is in
+    ///
+    /// Requires the `rpc` scope, see [`Client::authorize_and_authenticate`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub fn get_guilds(&mut self) -> Result<Vec<PartialGuild>> {
+        self.get_guilds_with_timeout(self.command_timeout)
+    }
+
+    /// Get the guilds the user is in, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn get_guilds_with_timeout(&mut self, timeout: Duration) -> Result<Vec<PartialGuild>> {
+        self.execute(request::get_guilds()?, timeout)
+    }
+
+    /// Get a channel the user can see
+    ///
+    /// Requires the `rpc` scope, see [`Client::authorize_and_authenticate`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub fn get_channel(&mut self, channel_id: u64) -> Result<Channel> {
+        self.get_channel_with_timeout(channel_id, self.command_timeout)
+    }
+
+    /// Get a channel the user can see, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn get_channel_with_timeout(
+        &mut self,
+        channel_id: u64,
+        timeout: Duration,
+    ) -> Result<Channel> {
+        self.execute(request::get_channel(channel_id)?, timeout)
+    }
+
+    /// Get the channels of a guild the user is in
+    ///
+    /// Requires the `rpc` scope, see [`Client::authorize_and_authenticate`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub fn get_channels(&mut self, guild_id: u64) -> Result<Vec<PartialChannel>> {
+        self.get_channels_with_timeout(guild_id, self.command_timeout)
+    }
+
+    /// Get the channels of a guild the user is in, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn get_channels_with_timeout(
+        &mut self,
+        guild_id: u64,
+        timeout: Duration,
+    ) -> Result<Vec<PartialChannel>> {
+        self.execute(request::get_channels(guild_id)?, timeout)
+    }
+
+    /// Join a voice channel, or leave the current one if `channel_id` is `None`
+    ///
+    /// Returns the channel that was joined.
+    /// Requires the `rpc` scope, see [`Client::authorize_and_authenticate`].
+    ///
+    /// # Errors
+    /// - The user is already in another voice channel, see [`SelectVoiceChannelArgs::force`]
+    /// - See [`DiscordError`] for more info
+    pub fn select_voice_channel(&mut self, channel_id: Option<u64>) -> Result<Option<Channel>> {
+        self.select_voice_channel_with_timeout(
+            SelectVoiceChannelArgs::new(channel_id),
+            self.command_timeout,
+        )
+    }
+
+    /// Join or leave a voice channel, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn select_voice_channel_with_timeout(
+        &mut self,
+        args: SelectVoiceChannelArgs,
+        timeout: Duration,
+    ) -> Result<Option<Channel>> {
+        self.execute(request::select_voice_channel(args)?, timeout)
+    }
+
+    /// Get the voice channel the user is in, if any
+    ///
+    /// Requires the `rpc` scope, see [`Client::authorize_and_authenticate`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub fn get_selected_voice_channel(&mut self) -> Result<Option<Channel>> {
+        self.get_selected_voice_channel_with_timeout(self.command_timeout)
+    }
+
+    /// Get the voice channel the user is in, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn get_selected_voice_channel_with_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<Channel>> {
+        self.execute(request::get_selected_voice_channel()?, timeout)
+    }
+
+    /// Join a text channel, or leave the current one if `channel_id` is `None`
+    ///
+    /// Returns the channel that was joined.
+    /// Requires the `rpc` scope, see [`Client::authorize_and_authenticate`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub fn select_text_channel(&mut self, channel_id: Option<u64>) -> Result<Option<Channel>> {
+        self.select_text_channel_with_timeout(
+            SelectTextChannelArgs::new(channel_id),
+            self.command_timeout,
+        )
+    }
+
+    /// Join or leave a text channel, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn select_text_channel_with_timeout(
+        &mut self,
+        args: SelectTextChannelArgs,
+        timeout: Duration,
+    ) -> Result<Option<Channel>> {
+        self.execute(request::select_text_channel(args)?, timeout)
+    }
+
+    /// Get the voice settings of the user
+    ///
+    /// Requires the `rpc` scope, see [`Client::authorize_and_authenticate`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub fn get_voice_settings(&mut self) -> Result<VoiceSettings> {
+        self.get_voice_settings_with_timeout(self.command_timeout)
+    }
+
+    /// Get the voice settings of the user, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn get_voice_settings_with_timeout(&mut self, timeout: Duration) -> Result<VoiceSettings> {
+        self.execute(request::get_voice_settings()?, timeout)
+    }
+
+    /// Change the voice settings of the user, e.g. to mute or deafen them
+    ///
+    /// Only the settings that are set are changed. Returns the new voice settings.
+    /// Requires the `rpc` scope, see [`Client::authorize_and_authenticate`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub fn set_voice_settings<F>(&mut self, f: F) -> Result<VoiceSettings>
+    where
+        F: FnOnce(VoiceSettings) -> VoiceSettings,
+    {
+        self.set_voice_settings_with_timeout(f, self.command_timeout)
+    }
+
+    /// Change the voice settings of the user, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn set_voice_settings_with_timeout<F>(
+        &mut self,
+        f: F,
+        timeout: Duration,
+    ) -> Result<VoiceSettings>
+    where
+        F: FnOnce(VoiceSettings) -> VoiceSettings,
+    {
+        self.execute(
+            request::set_voice_settings(f(VoiceSettings::new()))?,
+            timeout,
+        )
+    }
+
+    /// Change how the user hears another user, e.g. their volume
+    ///
+    /// Returns the new settings for that user.
+    /// Requires the `rpc` scope, see [`Client::authorize_and_authenticate`].
+    ///
+    /// # Errors
+    /// - See [`DiscordError`] for more info
+    pub fn set_user_voice_settings<F>(&mut self, user_id: u64, f: F) -> Result<UserVoiceSettings>
+    where
+        F: FnOnce(UserVoiceSettings) -> UserVoiceSettings,
+    {
+        self.set_user_voice_settings_with_timeout(user_id, f, self.command_timeout)
+    }
+
+    /// Change how the user hears another user, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn set_user_voice_settings_with_timeout<F>(
+        &mut self,
+        user_id: u64,
+        f: F,
+        timeout: Duration,
+    ) -> Result<UserVoiceSettings>
+    where
+        F: FnOnce(UserVoiceSettings) -> UserVoiceSettings,
+    {
         self.execute(
-            Command::CloseActivityRequest,
-            CloseActivityRequestArgs::new(user_id),
-            None,
+            request::set_user_voice_settings(user_id, f(UserVoiceSettings::new()))?,
+            timeout,
         )
     }
 
     /// Subscribe to a given event
     ///
+    /// The subscription is made again automatically after reconnecting, see [`ClientBuilder::restore_session`].
+    ///
     /// # Errors
     /// - See [`DiscordError`] for more info
     pub fn subscribe<F>(&mut self, evt: Event, f: F) -> Result<Payload<Subscription>>
     where
         F: FnOnce(SubscriptionArgs) -> SubscriptionArgs,
     {
-        self.execute(Command::Subscribe, f(SubscriptionArgs::new()), Some(evt))
+        self.subscribe_with_timeout(evt, f, self.command_timeout)
+    }
+
+    /// Subscribe to a given event, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn subscribe_with_timeout<F>(
+        &mut self,
+        evt: Event,
+        f: F,
+        timeout: Duration,
+    ) -> Result<Payload<Subscription>>
+    where
+        F: FnOnce(SubscriptionArgs) -> SubscriptionArgs,
+    {
+        let args = f(SubscriptionArgs::new());
+        let response = self.execute(request::subscribe(evt, args.clone())?, timeout)?;
+        self.connection_manager.session().subscribe(evt, args);
+
+        Ok(response)
     }
 
     /// Unsubscribe from a given event
@@ -267,7 +970,28 @@ impl Client {
     where
         F: FnOnce(SubscriptionArgs) -> SubscriptionArgs,
     {
-        self.execute(Command::Unsubscribe, f(SubscriptionArgs::new()), Some(evt))
+        self.unsubscribe_with_timeout(evt, f, self.command_timeout)
+    }
+
+    /// Unsubscribe from a given event, waiting at most `timeout` for Discord to respond
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if Discord did not respond in time
+    /// - See [`DiscordError`] for more info
+    pub fn unsubscribe_with_timeout<F>(
+        &mut self,
+        evt: Event,
+        f: F,
+        timeout: Duration,
+    ) -> Result<Payload<Subscription>>
+    where
+        F: FnOnce(SubscriptionArgs) -> SubscriptionArgs,
+    {
+        let args = f(SubscriptionArgs::new());
+        let response = self.execute(request::unsubscribe(evt, args.clone())?, timeout)?;
+        self.connection_manager.session().unsubscribe(evt, &args);
+
+        Ok(response)
     }
 
     /// Listens for a given event, and returns a handle that unregisters the listener when it is dropped.
@@ -334,62 +1058,531 @@ impl Client {
     where
         F: Fn(EventContext) + 'static + Send + Sync,
     {
+        if event == Event::Ready && self.replay_ready {
+            if let Some(ready) = self.ready_data() {
+                return self.event_handler_registry.register_and_replay(
+                    event,
+                    handler,
+                    EventData::Ready(ready),
+                );
+            }
+        }
+
         self.event_handler_registry.register(event, handler)
     }
 
+    #[must_use]
+    /// A stream of every event received from now on, as `(Event, EventData)` in arrival order
+    ///
+    /// Buffers up to 256 events, dropping the oldest ones when full.
+    /// Use [`Client::events_with_capacity`] to change this.
+    /// Can be used alongside, or instead of, event handlers.
+    ///
+    /// The stream ends once the client stops.
+    pub fn events(&self) -> Events {
+        self.events_with_capacity(DEFAULT_EVENT_CAPACITY, OverflowPolicy::default())
+    }
+
+    #[must_use]
+    /// A stream of every event received from now on, buffering at most `capacity` events
+    ///
+    /// When an event arrives while the buffer is full, `policy` decides which event is dropped.
+    pub fn events_with_capacity(&self, capacity: usize, policy: OverflowPolicy) -> Events {
+        self.event_handler_registry.stream(capacity, policy)
+    }
+
     /// Block the current thread until the event is fired
     ///
     /// Returns the context the event was fired in
     ///
     /// NOTE: Please only use this for the ready event, or if you know what you are doing.
+    /// If Discord is not running this may wait for a long time, see [`Client::block_until_event_timeout`].
     ///
     /// # Errors
-    /// - Channel disconnected
+    /// - [`DiscordError::ClientStopped`] if the client stopped before the event was fired
+    pub fn block_until_event(&self, event: Event) -> Result<crate::event_handler::Context> {
+        self.wait(event, None, |_| true)
+            .map(|data| EventContext::new(event, data))
+    }
+
+    /// Block the current thread until the event is fired, for at most `timeout`
+    ///
+    /// Returns the context the event was fired in
+    ///
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if the event was not fired in time
+    /// - [`DiscordError::ClientStopped`] if the client stopped before the event was fired
+    pub fn block_until_event_timeout(
+        &self,
+        event: Event,
+        timeout: Duration,
+    ) -> Result<crate::event_handler::Context> {
+        self.wait(event, Some(Instant::now() + timeout), |_| true)
+            .map(|data| EventContext::new(event, data))
+    }
+
+    /// Block the current thread until the event is fired with data matching `predicate`
+    ///
+    /// Returns the data of the matching event.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use discord_presence::{models::EventData, Client, Event};
+    /// let mut drpc = Client::new(1003450375732482138);
+    /// drpc.start();
+    ///
+    /// let join = drpc.wait_for(Event::ActivityJoin, |data| {
+    ///     matches!(data, EventData::ActivityJoin(join) if join.secret.is_some())
+    /// });
+    /// ```
+    ///
+    /// # Errors
+    /// - [`DiscordError::ClientStopped`] if the client stopped before a matching event was fired
+    pub fn wait_for<F>(&self, event: Event, predicate: F) -> Result<EventData>
+    where
+        F: Fn(&EventData) -> bool,
+    {
+        self.wait(event, None, predicate)
+    }
+
+    /// Block the current thread until the event is fired with data matching `predicate`, for at most `timeout`
     ///
-    /// # Panics
-    /// - Panics if the channel is disconnected for whatever reason.
-    pub fn block_until_event(&mut self, event: Event) -> Result<crate::event_handler::Context> {
-        // TODO: Use bounded channel
-        let (tx, rx) = crossbeam_channel::unbounded::<crate::event_handler::Context>();
+    /// # Errors
+    /// - [`DiscordError::TimeoutError`] if no matching event was fired in time
+    /// - [`DiscordError::ClientStopped`] if the client stopped before a matching event was fired
+    pub fn wait_for_timeout<F>(
+        &self,
+        event: Event,
+        timeout: Duration,
+        predicate: F,
+    ) -> Result<EventData>
+    where
+        F: Fn(&EventData) -> bool,
+    {
+        self.wait(event, Some(Instant::now() + timeout), predicate)
+    }
 
-        let handler = move |info| {
-            // dbg!("Blocked until at ", std::time::SystemTime::now());
-            if let Err(e) = tx.send(info) {
-                error!("{e}");
+    fn wait<F>(&self, event: Event, deadline: Option<Instant>, predicate: F) -> Result<EventData>
+    where
+        F: Fn(&EventData) -> bool,
+    {
+        let events = self.events();
+
+        if event == Event::Ready && self.replay_ready {
+            if let Some(data) = self.ready_data().map(EventData::Ready) {
+                if predicate(&data) {
+                    return Ok(data);
+                }
             }
-        };
+        }
 
-        // `handler` is automatically unregistered once this variable drops
-        let cb_handle = self.on_event(event, handler);
+        loop {
+            let received = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(RecvTimeoutError::Timeout.into());
+                    }
 
-        let response = rx.recv()?;
+                    events.recv_timeout(remaining)
+                }
+                None => events.recv(),
+            };
 
-        drop(cb_handle);
+            match received {
+                Some((kind, data)) if kind == event && predicate(&data) => return Ok(data),
+                None if events.is_finished() => return Err(DiscordError::ClientStopped),
+                _ => {}
+            }
+        }
+    }
 
-        Ok(response)
+    /// Listens for any event whose data could not be parsed, or that the crate does not know,
+    /// with the name Discord sent the event with, e.g. `ACTIVITY_JOIN`, and its raw JSON data
+    ///
+    /// The typed handlers, such as `on_ready`, are not called for these events.
+    pub fn on_raw_event<F>(&self, handler: F) -> EventCallbackHandle
+    where
+        F: Fn(&str, Value) + 'static + Send + Sync,
+    {
+        self.event_handler_registry
+            .register_raw(move |ctx: EventContext| {
+                if let EventData::Unknown(data) = ctx.event {
+                    handler(&ctx.kind.name(), data);
+                }
+            })
     }
 
-    event_handler_function!(on_ready, Event::Ready);
+    event_handler_function!(on_ready, Event::Ready, Ready(ReadyEvent));
+
+    event_handler_function!(on_error, Event::Error, Error(ErrorEvent));
+
+    event_handler_function!(
+        on_activity_join,
+        Event::ActivityJoin,
+        ActivityJoin(ActivityJoinEvent)
+    );
+
+    event_handler_function!(
+        on_activity_join_request,
+        Event::ActivityJoinRequest,
+        ActivityJoinRequest(ActivityJoinRequestEvent)
+    );
+
+    event_handler_function!(
+        on_activity_spectate,
+        Event::ActivitySpectate,
+        ActivitySpectate(ActivitySpectateEvent)
+    );
+
+    event_handler_function!(
+        on_activity_invite,
+        Event::ActivityInvite,
+        ActivityInvite(ActivityInviteEvent)
+    );
+
+    event_handler_function!(
+        on_voice_state_create,
+        Event::VoiceStateCreate,
+        VoiceState(VoiceStateEvent)
+    );
+
+    event_handler_function!(
+        on_voice_state_update,
+        Event::VoiceStateUpdate,
+        VoiceState(VoiceStateEvent)
+    );
+
+    event_handler_function!(
+        on_voice_state_delete,
+        Event::VoiceStateDelete,
+        VoiceState(VoiceStateEvent)
+    );
+
+    event_handler_function!(
+        on_speaking_start,
+        Event::SpeakingStart,
+        Speaking(SpeakingEvent)
+    );
+
+    event_handler_function!(
+        on_speaking_stop,
+        Event::SpeakingStop,
+        Speaking(SpeakingEvent)
+    );
+
+    event_handler_function!(
+        on_voice_connection_status,
+        Event::VoiceConnectionStatus,
+        VoiceConnectionStatus(VoiceConnectionStatusEvent)
+    );
 
-    event_handler_function!(on_error, Event::Error);
+    event_handler_function!(
+        on_message_create,
+        Event::MessageCreate,
+        Message(MessageEvent)
+    );
 
-    event_handler_function!(on_activity_join, Event::ActivityJoin);
+    event_handler_function!(
+        on_message_update,
+        Event::MessageUpdate,
+        Message(MessageEvent)
+    );
 
-    event_handler_function!(on_activity_join_request, Event::ActivityJoinRequest);
+    event_handler_function!(
+        on_message_delete,
+        Event::MessageDelete,
+        Message(MessageEvent)
+    );
 
-    event_handler_function!(on_activity_spectate, Event::ActivitySpectate);
+    event_handler_function!(
+        on_guild_status,
+        Event::GuildStatus,
+        GuildStatus(GuildStatusEvent)
+    );
+
+    event_handler_function!(
+        on_guild_create,
+        Event::GuildCreate,
+        GuildCreate(GuildCreateEvent)
+    );
+
+    event_handler_function!(
+        on_channel_create,
+        Event::ChannelCreate,
+        ChannelCreate(ChannelCreateEvent)
+    );
+
+    event_handler_function!(
+        on_notification_create,
+        Event::NotificationCreate,
+        NotificationCreate(NotificationCreateEvent)
+    );
+
+    event_handler_function!(
+        on_connected,
+        Event::Connected,
+        ConnectionState(ConnectionStateEvent)
+    );
+
+    event_handler_function!(
+        on_disconnected,
+        Event::Disconnected,
+        ConnectionState(ConnectionStateEvent)
+    );
+
+    event_handler_function!(
+        on_connection_state_changed,
+        Event::ConnectionStateChanged,
+        ConnectionState(ConnectionStateEvent)
+    );
+
+    event_handler_function!(
+        on_reconnect_failed,
+        Event::ReconnectFailed,
+        ReconnectFailed(ReconnectFailedEvent)
+    );
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::OpCode;
 
     #[test]
     fn test_is_ready() {
-        assert!(!Client::is_ready());
+        let client = Client::new(1);
+        let other = Client::new(2);
+        assert!(!client.is_ready());
+
+        client.connection_manager.set_ready(true);
+
+        assert!(client.is_ready());
+        assert!(client.clone().is_ready());
+        assert!(!other.is_ready());
+    }
+
+    #[test]
+    fn test_command_timeout() {
+        let mut client = Client::builder(1)
+            .command_timeout(Duration::from_millis(10))
+            .build();
+        client.connection_manager.set_ready(true);
+
+        // The client was never started, so Discord will never respond
+        let result = client.clear_activity();
+
+        assert!(matches!(result, Err(DiscordError::TimeoutError(_))));
+        assert!(matches!(
+            client.set_activity_with_timeout(|a| a, Duration::from_millis(10)),
+            Err(DiscordError::TimeoutError(_))
+        ));
+    }
+
+    #[test]
+    fn typed_handlers_receive_parsed_data() {
+        let client = Client::builder(1)
+            .dispatch_mode(DispatchMode::Inline)
+            .build();
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let raw_tx = tx.clone();
+        let _ready = client.on_ready(move |ready| tx.send(ready.v).unwrap());
+        let _raw = client.on_raw_event(move |event, _| {
+            assert_eq!(event, "READY");
+            raw_tx.send(None).unwrap();
+        });
+
+        let registry = &client.event_handler_registry;
+        registry.handle(
+            Event::Ready,
+            Event::Ready.parse_data(serde_json::json!({ "v": 1 })),
+        );
+        registry.handle(
+            Event::Ready,
+            Event::Ready.parse_data(serde_json::json!({ "v": "one" })),
+        );
+
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![Some(1), None]);
+    }
+
+    #[test]
+    fn block_until_event_times_out() {
+        let client = Client::new(1);
+
+        // The client was never started, so nothing will ever be fired
+        let result = client.block_until_event_timeout(Event::Ready, Duration::from_millis(10));
+
+        assert!(matches!(result, Err(DiscordError::TimeoutError(_))));
+    }
+
+    #[test]
+    fn block_until_event_fails_once_stopped() {
+        let mut client = Client::builder(1)
+            .ipc_path(std::env::temp_dir().join("discord-presence-missing.sock"))
+            .reconnect_policy(ReconnectPolicy::Never)
+            .build();
+        client.start();
 
-        crate::READY.store(true, Ordering::Relaxed);
+        // Discord is not there, so the client gives up right away
+        let result = client.block_until_event_timeout(Event::Ready, Duration::from_secs(5));
 
-        assert!(Client::is_ready());
+        assert!(matches!(result, Err(DiscordError::ClientStopped)));
+    }
+
+    #[test]
+    fn stops_reconnecting_once_dropped() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let mut client = Client::builder(1)
+            .connector(
+                move || -> Result<crate::StreamTransport<std::net::TcpStream>> {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into())
+                },
+            )
+            .reconnect_policy(ReconnectPolicy::Fixed {
+                interval: Duration::from_millis(100),
+                max_attempts: None,
+            })
+            .build();
+        client.start();
+
+        std::thread::sleep(Duration::from_millis(250));
+        drop(client);
+        std::thread::sleep(Duration::from_millis(100));
+        let after_drop = attempts.load(Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(500));
+
+        // Retried every interval while alive, then never again
+        assert!((2..=4).contains(&after_drop), "{} attempts", after_drop);
+        assert_eq!(attempts.load(Ordering::SeqCst), after_drop);
+    }
+
+    #[test]
+    fn wait_for_matches_predicate() {
+        let client = Client::new(1);
+        let registry = client.event_handler_registry.clone();
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        let fire = {
+            let done = done.clone();
+            std::thread::spawn(move || {
+                // Keep firing, as the events fired before `wait_for` starts listening are missed
+                for code in (0..3).cycle() {
+                    if done.load(std::sync::atomic::Ordering::SeqCst) {
+                        break;
+                    }
+                    registry.handle(Event::Error, EventData::Error(ErrorEvent::new().code(code)));
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            })
+        };
+
+        let result = client.wait_for_timeout(Event::Error, Duration::from_secs(5), |data| {
+            matches!(data, EventData::Error(ErrorEvent { code: Some(2), .. }))
+        });
+        done.store(true, std::sync::atomic::Ordering::SeqCst);
+        fire.join().unwrap();
+
+        assert_eq!(result.unwrap(), EventData::Error(ErrorEvent::new().code(2)));
+    }
+
+    #[test]
+    fn replays_ready_to_late_handlers() {
+        let ready = ReadyEvent::new()
+            .v(1)
+            .user(PartialUser::new().username("rustacean"));
+
+        for replay in [false, true] {
+            let client = Client::builder(1)
+                .dispatch_mode(DispatchMode::Inline)
+                .replay_ready(replay)
+                .build();
+            client.connection_manager.set_ready_data(ready.clone());
+
+            let (tx, rx) = crossbeam_channel::unbounded();
+            let _ready = client.on_ready(move |ready| tx.send(ready).unwrap());
+
+            assert_eq!(
+                client.current_user().unwrap().username.as_deref(),
+                Some("rustacean")
+            );
+            assert_eq!(rx.try_recv().ok(), replay.then(|| ready.clone()));
+            assert_eq!(
+                client
+                    .block_until_event_timeout(Event::Ready, Duration::from_millis(10))
+                    .is_ok(),
+                replay
+            );
+        }
+    }
+
+    #[test]
+    fn replays_recorded_transcript() {
+        use crate::{MessageDirection, TranscriptEntry};
+        use serde_json::json;
+
+        let entry = |direction, opcode, payload| {
+            serde_json::to_string(&TranscriptEntry {
+                direction,
+                opcode,
+                payload,
+                timestamp: 0,
+            })
+            .unwrap()
+        };
+        let lines = [
+            entry(
+                MessageDirection::Sent,
+                OpCode::Handshake,
+                json!({ "v": 1, "client_id": "1" }),
+            ),
+            entry(
+                MessageDirection::Received,
+                OpCode::Frame,
+                json!({ "cmd": "DISPATCH", "evt": "READY", "data": { "v": 1 }, "nonce": null }),
+            ),
+            entry(
+                MessageDirection::Sent,
+                OpCode::Frame,
+                json!({ "cmd": "SET_ACTIVITY", "nonce": "recorded" }),
+            ),
+            entry(
+                MessageDirection::Received,
+                OpCode::Frame,
+                json!({ "cmd": "SET_ACTIVITY", "data": { "state": "replayed" }, "evt": null, "nonce": "recorded" }),
+            ),
+        ];
+        let path = std::env::temp_dir().join(format!(
+            "discord-presence-transcript-{}.jsonl",
+            crate::utils::nonce()
+        ));
+        std::fs::write(&path, lines.join("\n")).unwrap();
+
+        let mut client = Client::builder(1).replay_transcript(&path).build();
+        client.start();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !client.is_ready() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let ready = client.is_ready();
+        let unrecorded = client.get_guilds();
+        let activity = client.set_activity(|act| act.state("replayed"));
+        client.shutdown().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(ready);
+        assert!(matches!(
+            unrecorded,
+            Err(DiscordError::TranscriptMismatch { expected, actual })
+                if expected.contains("SET_ACTIVITY") && actual.contains("GET_GUILDS")
+        ));
+        assert_eq!(
+            activity.unwrap().data.unwrap().state.as_deref(),
+            Some("replayed")
+        );
     }
 }
@@ -1,3 +1,4 @@
+use super::transcript::{Replay, Transcript};
 use crate::{
     error::{DiscordError, Result},
     models::message::{Message, OpCode},
@@ -6,12 +7,70 @@ use crate::{
 use bytes::BytesMut;
 use serde_json::json;
 use std::{
+    env,
     io::{Read, Write},
     marker::Sized,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::Arc,
     thread, time,
 };
 
+/// The number of IPC slots Discord may listen on, `discord-ipc-0` through `discord-ipc-9`.
+pub const IPC_SLOTS: u8 = 10;
+
+/// Environment variable that overrides the path of the IPC socket, see [`ConnectionConfig::ipc_path`].
+pub const IPC_PATH_ENV: &str = "DISCORD_IPC_PATH";
+
+/// How long shutting down waits for Discord, unless set with [`ConnectionConfig::shutdown_timeout`].
+pub const DEFAULT_SHUTDOWN_TIMEOUT: time::Duration = time::Duration::from_millis(500);
+
+/// Options controlling where a connection looks for Discord, and how it talks to it
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionConfig {
+    /// Exact path of the IPC socket to connect to, skipping the search entirely
+    ///
+    /// Falls back to the [`IPC_PATH_ENV`] environment variable if not set.
+    pub ipc_path: Option<PathBuf>,
+    /// Extra directories searched for the IPC socket, before the default ones
+    pub search_dirs: Vec<PathBuf>,
+    /// Transcript every message is recorded to, if any
+    pub record: Option<Transcript>,
+    /// Transcript played back instead of connecting to Discord, if any
+    pub replay: Option<Replay>,
+    /// Opens a custom transport instead of searching for the IPC socket, if any
+    pub connector: Option<Connector>,
+    /// Whether the activity is cleared before the connection is closed on shutdown
+    pub clear_activity_on_shutdown: bool,
+    /// How long shutting down waits for Discord, falling back to [`DEFAULT_SHUTDOWN_TIMEOUT`]
+    pub shutdown_timeout: Option<time::Duration>,
+}
+
+/// Opens a new [`Transport`], on every connection attempt
+#[derive(Clone)]
+pub struct Connector(Arc<dyn Fn() -> Result<Box<dyn Transport>> + Send + Sync>);
+
+impl Connector {
+    pub fn new<F, T>(connect: F) -> Self
+    where
+        F: Fn() -> Result<T> + Send + Sync + 'static,
+        T: Transport + 'static,
+    {
+        Self(Arc::new(move || {
+            connect().map(|transport| Box::new(transport) as Box<dyn Transport>)
+        }))
+    }
+
+    pub fn connect(&self) -> Result<Box<dyn Transport>> {
+        (self.0)()
+    }
+}
+
+impl std::fmt::Debug for Connector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connector").finish_non_exhaustive()
+    }
+}
+
 /// Wait for a non-blocking connection until it's complete.
 macro_rules! try_until_done {
     [ $e:expr ] => {
@@ -26,48 +85,103 @@ macro_rules! try_until_done {
     }
 }
 
+/// The first message sent to Discord on a new connection, identifying the application.
+pub fn handshake_message(client_id: u64) -> Result<Message> {
+    let hs = json![{
+        "client_id": client_id.to_string(),
+        "v": 1,
+        "nonce": utils::nonce()
+    }];
+
+    Message::new(OpCode::Handshake, hs)
+}
+
+/// The paths to try connecting to, in order, along with the slot each path is for.
+///
+/// This is exactly the configured IPC path if there is one.
+/// Otherwise it is every slot from `discord-ipc-0` to `discord-ipc-9` in each search directory,
+/// starting with the extra directories in `config`, then `default_dirs`.
+pub fn candidates_in(
+    config: &ConnectionConfig,
+    default_dirs: Vec<PathBuf>,
+) -> Vec<(PathBuf, Option<u8>)> {
+    let ipc_path = config
+        .ipc_path
+        .clone()
+        .or_else(|| env::var_os(IPC_PATH_ENV).map(PathBuf::from));
+
+    if let Some(path) = ipc_path {
+        return vec![(path, None)];
+    }
+
+    config
+        .search_dirs
+        .iter()
+        .cloned()
+        .chain(default_dirs)
+        .flat_map(|dir| {
+            (0..IPC_SLOTS).map(move |n| (dir.join(format!("discord-ipc-{n}")), Some(n)))
+        })
+        .collect()
+}
+
 pub trait Connection: Sized {
     type Socket: Write + Read;
 
     /// The internally stored socket connection.
     fn socket(&mut self) -> &mut Self::Socket;
 
+    /// Bytes read from the socket that do not form a complete frame yet.
+    fn read_buffer(&mut self) -> &mut BytesMut;
+
     /// The base path were the socket is located.
     fn ipc_path() -> PathBuf;
 
+    /// The directories searched for the IPC socket, in order of preference.
+    fn search_dirs() -> Vec<PathBuf> {
+        vec![Self::ipc_path()]
+    }
+
+    /// Establish a new connection to the socket at the given path.
+    ///
+    /// `slot` is `None` if the path was given explicitly, rather than found by searching.
+    fn connect_to(path: &Path, slot: Option<u8>) -> Result<Self>;
+
+    /// The slot this connection was established on, if it was found by searching.
+    fn slot(&self) -> Option<u8>;
+
+    /// The paths to try connecting to, in order, along with the slot each path is for.
+    ///
+    /// See [`candidates_in`], searching the default directories of this platform.
+    fn candidates(config: &ConnectionConfig) -> Vec<(PathBuf, Option<u8>)> {
+        candidates_in(config, Self::search_dirs())
+    }
+
     /// Establish a new connection to the server.
-    fn connect() -> Result<Self>;
-
-    /// The full socket path.
-    fn socket_path(n: u8) -> PathBuf {
-        let socket_path = format!("discord-ipc-{n}");
-        let base_path = Self::ipc_path().join(socket_path.clone());
-
-        if base_path.exists() {
-            base_path
-        } else {
-            // This fixes issues with Unix implementations
-            Self::ipc_path()
-                .join("app")
-                .join("com.discordapp.Discord")
-                .join(socket_path)
-        }
+    ///
+    /// Tries each of [`Connection::candidates`] in turn, and returns the first connection that succeeds.
+    fn connect(config: &ConnectionConfig) -> Result<Self> {
+        Self::connect_any(Self::candidates(config))
     }
 
-    /// Perform a handshake on this socket connection.
-    /// Will block until complete.
-    fn handshake(&mut self, client_id: u64) -> Result<Message> {
-        let hs = json![{
-            "client_id": client_id.to_string(),
-            "v": 1,
-            "nonce": utils::nonce()
-        }];
+    /// Tries each of `candidates` in turn, and returns the first connection that succeeds.
+    fn connect_any(candidates: Vec<(PathBuf, Option<u8>)>) -> Result<Self> {
+        let mut last_error = None;
 
-        let msg = Message::new(OpCode::Handshake, hs)?;
-        try_until_done!(self.send(&msg));
-        let msg = try_until_done!(self.recv());
+        for (path, slot) in candidates {
+            match Self::connect_to(&path, slot) {
+                Ok(connection) => {
+                    trace!("Connected to {}", path.display());
+                    return Ok(connection);
+                }
+                Err(why) => {
+                    trace!("Failed to connect to {}: {}", path.display(), why);
+                    last_error = Some(why);
+                }
+            }
+        }
 
-        Ok(msg)
+        Err(last_error.unwrap_or(DiscordError::ConnectionClosed))
     }
 
     /// Ping the server and get a pong response.
@@ -81,30 +195,113 @@ pub trait Connection: Sized {
 
     /// Send a message to the server.
     fn send(&mut self, message: &Message) -> Result<()> {
-        match message.encode() {
-            Err(why) => error!("{:?}", why),
-            Ok(bytes) => {
-                self.socket().write_all(&bytes)?;
-            }
-        };
-        trace!("-> {:?}", message);
-        Ok(())
+        write_frame(self.socket(), message)
     }
 
     /// Receive a message from the server.
     fn recv(&mut self) -> Result<Message> {
-        let mut buf = BytesMut::new();
-        buf.resize(1024, 0);
-        let n = self.socket().read(&mut buf)?;
+        // Taken out while reading, as the socket borrows `self` too
+        let mut read_buffer = std::mem::take(self.read_buffer());
+        let result = read_frame(self.socket(), &mut read_buffer);
+        *self.read_buffer() = read_buffer;
+
+        result
+    }
+}
+
+/// Write a message to a stream.
+pub fn write_frame<S>(socket: &mut S, message: &Message) -> Result<()>
+where
+    S: Write,
+{
+    match message.encode() {
+        Err(why) => error!("{:?}", why),
+        Ok(bytes) => {
+            socket.write_all(&bytes)?;
+        }
+    };
+    trace!("-> {:?}", message);
+    Ok(())
+}
+
+/// Read a message from a stream.
+///
+/// Reads until a complete frame is buffered, keeping any bytes after it for the next call.
+pub fn read_frame<S>(socket: &mut S, read_buffer: &mut BytesMut) -> Result<Message>
+where
+    S: Read,
+{
+    loop {
+        if let Some(message) = Message::decode_frame(read_buffer)? {
+            trace!("<- {:?}", message);
+            return Ok(message);
+        }
+
+        let mut buf = [0; 1024];
+        let n = socket.read(&mut buf)?;
         trace!("Received {} bytes", n);
 
         if n == 0 {
             return Err(DiscordError::ConnectionClosed);
         }
 
-        let message = Message::decode(&buf[..n])?;
-        trace!("<- {:?}", message);
+        read_buffer.extend_from_slice(&buf[..n]);
+    }
+}
+
+/// A connection to Discord, carrying whole messages
+///
+/// The IPC sockets are the default transport. Implement this to connect to Discord some other way,
+/// or use [`StreamTransport`](crate::StreamTransport) for any [`Read`] + [`Write`] stream,
+/// then set it with [`ClientBuilder::connector`](crate::ClientBuilder::connector).
+pub trait Transport: Send {
+    /// Send a message to the server.
+    ///
+    /// # Errors
+    /// - The message could not be sent
+    fn send(&mut self, message: &Message) -> Result<()>;
+
+    /// Receive a message from the server.
+    ///
+    /// This should not block: the client sends its commands between calls.
+    ///
+    /// # Errors
+    /// - [`DiscordError::IoError`] with [`std::io::ErrorKind::WouldBlock`] if no message is available yet
+    /// - [`DiscordError::ConnectionClosed`] if the server closed the connection
+    fn recv(&mut self) -> Result<Message>;
+
+    /// The IPC slot this connection was established on, if it was found by searching.
+    fn slot(&self) -> Option<u8> {
+        None
+    }
+
+    /// Perform a handshake on this connection.
+    /// Will block until complete.
+    ///
+    /// # Errors
+    /// - The handshake could not be sent, or no reply was received
+    fn handshake(&mut self, client_id: u64) -> Result<Message> {
+        let msg = handshake_message(client_id)?;
+        try_until_done!(self.send(&msg));
+        let msg = try_until_done!(self.recv());
+
+        Ok(msg)
+    }
+}
+
+impl<C> Transport for C
+where
+    C: Connection + Send,
+{
+    fn send(&mut self, message: &Message) -> Result<()> {
+        Connection::send(self, message)
+    }
+
+    fn recv(&mut self) -> Result<Message> {
+        Connection::recv(self)
+    }
 
-        Ok(message)
+    fn slot(&self) -> Option<u8> {
+        Connection::slot(self)
     }
 }
@@ -1,49 +1,90 @@
-use super::{Connection, Socket};
+use super::{
+    session::Session, Connection, ConnectionConfig, ConnectionState, ReconnectPolicy, Recorder,
+    Socket, Transport, DEFAULT_SHUTDOWN_TIMEOUT,
+};
 use crate::{
     error::{DiscordError, Result},
     event_handler::HandlerRegistry,
-    models::{payload::Payload, ErrorEvent, Event, Message},
+    models::{
+        payload::Payload, rich_presence::SetActivityArgs, Command, ErrorEvent, Event, EventData,
+        Message, OpCode, ReadyEvent, ReconnectFailedEvent,
+    },
+    request,
 };
-use crossbeam_channel::{unbounded, Receiver, Sender};
-use parking_lot::Mutex;
+use crossbeam_channel::{bounded, unbounded, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use parking_lot::{Mutex, MutexGuard};
 use serde_json::Value as JsonValue;
 use std::{
+    collections::{HashMap, HashSet},
     io::ErrorKind,
-    sync::{atomic::Ordering, Arc},
-    thread, time,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+    time::{self, Instant},
 };
 
 type Tx = Sender<Message>;
 type Rx = Receiver<Message>;
+/// The response to a command, or why it could not be sent
+type ResponseRx = Receiver<Result<Message>>;
+
+/// Connection state shared between all clones of a single [`Manager`]
+#[derive(Debug, Default)]
+struct SharedState {
+    ready: AtomicBool,
+    slot: Mutex<Option<u8>>,
+    connection_state: Mutex<ConnectionState>,
+    session: Mutex<Session>,
+    /// The data of the last handshake
+    ready_data: Mutex<Option<ReadyEvent>>,
+    /// Commands waiting for a response, by nonce
+    pending: Mutex<HashMap<String, Sender<Result<Message>>>>,
+    /// Commands sent to restore the session, by nonce, whose responses nobody waits for
+    restoring: Mutex<HashSet<String>>,
+}
 
 // TODO: Refactor connection manager
 #[derive(Clone)]
 pub struct Manager {
-    connection: Arc<Option<Mutex<Socket>>>,
+    connection: Arc<Option<Mutex<Box<dyn Transport>>>>,
+    state: Arc<SharedState>,
     client_id: u64,
+    config: ConnectionConfig,
+    reconnect_policy: ReconnectPolicy,
+    restore_session: bool,
     outbound: (Rx, Tx),
-    inbound: (Rx, Tx),
     handshake_completed: bool,
     event_handler_registry: Arc<HandlerRegistry>,
 }
 
 impl Manager {
-    pub fn new(client_id: u64, event_handler_registry: Arc<HandlerRegistry>) -> Self {
+    pub fn new(
+        client_id: u64,
+        config: ConnectionConfig,
+        reconnect_policy: ReconnectPolicy,
+        restore_session: bool,
+        event_handler_registry: Arc<HandlerRegistry>,
+    ) -> Self {
         let connection = Arc::new(None);
         let (sender_o, receiver_o) = unbounded();
-        let (sender_i, receiver_i) = unbounded();
 
         Self {
             connection,
+            state: Arc::new(SharedState::default()),
             client_id,
+            config,
+            reconnect_policy,
+            restore_session,
             handshake_completed: false,
-            inbound: (receiver_i, sender_i),
             outbound: (receiver_o, sender_o),
             event_handler_registry,
         }
     }
 
     pub fn start(&mut self, rx: Receiver<()>) -> std::thread::JoinHandle<()> {
+        self.event_handler_registry.reopen_streams();
         let mut manager_inner = self.clone();
         thread::spawn(move || {
             // TODO: Refactor so that JSON values are consistent across errors
@@ -57,8 +98,113 @@ impl Manager {
         Ok(())
     }
 
-    pub fn recv(&self) -> Result<Message> {
-        self.inbound.0.recv().map_err(DiscordError::from)
+    /// Sends a command, returning a receiver for the response with the same nonce
+    pub fn request(&self, nonce: &str, message: Message) -> Result<ResponseRx> {
+        let (tx, rx) = bounded(1);
+        self.state.pending.lock().insert(nonce.to_owned(), tx);
+
+        if let Err(why) = self.send(message) {
+            self.state.pending.lock().remove(nonce);
+            return Err(why);
+        }
+
+        Ok(rx)
+    }
+
+    /// Stops waiting for the response to a command
+    pub fn cancel(&self, nonce: &str) {
+        self.state.pending.lock().remove(nonce);
+    }
+
+    /// Routes a message from Discord to the command waiting for it, or to the event handlers
+    fn handle_message(&self, msg: Message) -> Result<()> {
+        request::route_message(msg, &self.event_handler_registry, |nonce, msg| {
+            let Some(tx) = self.state.pending.lock().remove(nonce) else {
+                if !self.state.restoring.lock().remove(nonce) {
+                    return false;
+                }
+
+                self.restored(&msg);
+                return true;
+            };
+
+            // The caller may have given up waiting already
+            let _ = tx.send(Ok(msg));
+            true
+        })
+    }
+
+    /// Reports a command sent to restore the session that Discord failed as an error event
+    ///
+    /// Nobody waits for these responses, so the handlers are the only way to learn of a lost subscription.
+    fn restored(&self, msg: &Message) {
+        let Ok(payload) = serde_json::from_str::<Payload<JsonValue>>(&msg.payload) else {
+            return;
+        };
+
+        if payload.evt == Some(Event::Error) {
+            error!("Failed to restore {:?}: {:?}", payload.cmd, payload.data);
+            let data = Event::Error.parse_data(payload.data.unwrap_or_default());
+            self.event_handler_registry.handle(Event::Error, data);
+        }
+    }
+
+    /// Fails the command a message was sent for, rather than leaving it waiting for a response that never comes
+    fn fail(&self, msg: &Message, why: DiscordError) {
+        let pending = nonce(msg).and_then(|nonce| self.state.pending.lock().remove(&nonce));
+
+        if let Some(tx) = pending {
+            // The caller may have given up waiting already
+            let _ = tx.send(Err(why));
+        } else {
+            error!("Failed to send message: {}", why);
+        }
+    }
+
+    /// Whether the handshake with Discord has completed
+    pub fn is_ready(&self) -> bool {
+        self.state.ready.load(Ordering::Relaxed)
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.state.ready.store(ready, Ordering::Relaxed);
+    }
+
+    /// The IPC slot of the current connection, if connected
+    pub fn slot(&self) -> Option<u8> {
+        *self.state.slot.lock()
+    }
+
+    /// The activity and subscriptions to restore after reconnecting
+    pub fn session(&self) -> MutexGuard<'_, Session> {
+        self.state.session.lock()
+    }
+
+    /// The data Discord sent when the last handshake completed
+    pub fn ready_data(&self) -> Option<ReadyEvent> {
+        self.state.ready_data.lock().clone()
+    }
+
+    pub fn set_ready_data(&self, ready: ReadyEvent) {
+        *self.state.ready_data.lock() = Some(ready);
+    }
+
+    pub fn connection_state(&self) -> ConnectionState {
+        *self.state.connection_state.lock()
+    }
+
+    /// Moves the connection to a new state, firing the appropriate events
+    fn set_connection_state(&self, current: ConnectionState) {
+        let previous = std::mem::replace(&mut *self.state.connection_state.lock(), current);
+
+        if previous != current {
+            // Only an actual connection can be lost, not a connection attempt that failed
+            self.event_handler_registry.connection_state_changed(
+                previous,
+                current,
+                self.connection.is_some(),
+            );
+        }
     }
 
     fn connect(&mut self) -> Result<()> {
@@ -67,48 +213,199 @@ impl Manager {
         }
 
         trace!("Connecting");
+        self.set_connection_state(ConnectionState::Connecting);
 
-        let mut new_connection = Socket::connect()?;
+        let mut new_connection: Box<dyn Transport> =
+            match (&self.config.replay, &self.config.connector) {
+                (Some(replay), _) => Box::new(replay.connect()?),
+                (None, Some(connector)) => connector.connect()?,
+                (None, None) => Box::new(Socket::connect(&self.config)?),
+            };
+
+        if let Some(transcript) = &self.config.record {
+            new_connection = Box::new(Recorder::new(new_connection, transcript.clone()));
+        }
 
         trace!("Performing handshake");
+        self.set_connection_state(ConnectionState::Handshaking);
         let msg = new_connection.handshake(self.client_id)?;
         let payload: Payload<JsonValue> = serde_json::from_str(&msg.payload)?;
+        let data = Event::Ready.parse_data(into_error!(payload.data)?);
+
+        // Kept on every handshake, as the user may have changed while Discord restarted
+        if let EventData::Ready(ready) = &data {
+            self.set_ready_data(ready.clone());
+        }
 
-        // TODO: Ensure it works without clone
         // Only handle the ready event if the client was not already ready
-        if !crate::READY.load(std::sync::atomic::Ordering::Relaxed) {
+        if !self.is_ready() {
             trace!("Discord client is ready!");
-            crate::READY.store(true, Ordering::Relaxed);
+            self.set_ready(true);
 
-            self.event_handler_registry.handle(
-                Event::Ready,
-                Event::Ready.parse_data(into_error!(payload.data)?),
-            );
+            self.event_handler_registry.handle(Event::Ready, data);
         }
 
         trace!("Handshake completed");
 
+        if self.restore_session {
+            self.restore_previous_session(&mut new_connection);
+        }
+
+        *self.state.slot.lock() = new_connection.slot();
         self.connection = Arc::new(Some(Mutex::new(new_connection)));
 
         trace!("Connected");
+        self.set_connection_state(ConnectionState::Connected);
 
         Ok(())
     }
 
+    /// Re-applies the activity and subscriptions set before the connection was lost
+    fn restore_previous_session(&self, connection: &mut Box<dyn Transport>) {
+        let messages = match self.session().messages() {
+            Ok(messages) => messages,
+            Err(why) => {
+                error!("Failed to restore session: {}", why);
+                return;
+            }
+        };
+
+        if !messages.is_empty() {
+            trace!("Restoring session");
+        }
+
+        // Responses to an earlier restore will never come on the new connection
+        let mut restoring = self.state.restoring.lock();
+        restoring.clear();
+
+        for message in messages {
+            if let Err(why) = connection.send(&message) {
+                error!("Failed to restore session: {}", why);
+            } else if let Some(nonce) = nonce(&message) {
+                restoring.insert(nonce);
+            }
+        }
+    }
+
+    /// Closes the connection the way Discord expects, rather than just dropping the socket
+    ///
+    /// Sends the messages still queued, clears the activity if configured to,
+    /// then sends a close frame and waits for Discord to acknowledge it, all within the shutdown timeout.
+    fn close_gracefully(&self, outbound: &Rx) {
+        let connection = self.connection.clone();
+        let Some(connection) = &*connection else {
+            return;
+        };
+
+        let timeout = self
+            .config
+            .shutdown_timeout
+            .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+
+        let result = self.close(&mut connection.lock(), outbound, Instant::now() + timeout);
+
+        if let Err(why) = result {
+            trace!("Failed to close the connection gracefully: {}", why);
+        }
+    }
+
+    fn close(
+        &self,
+        connection: &mut Box<dyn Transport>,
+        outbound: &Rx,
+        deadline: Instant,
+    ) -> Result<()> {
+        while let Ok(msg) = outbound.try_recv() {
+            connection.send(&msg)?;
+        }
+
+        if self.config.clear_activity_on_shutdown {
+            trace!("Clearing activity");
+            let payload = Payload::with_nonce(
+                Command::SetActivity,
+                Some(SetActivityArgs::default()),
+                None,
+                None,
+            );
+            let nonce = payload.nonce.clone();
+            connection.send(&Message::new(OpCode::Frame, payload)?)?;
+            self.session().clear_activity();
+
+            // Otherwise Discord may close the connection before clearing the activity
+            self.receive_until(connection, deadline, |msg| {
+                serde_json::from_str::<Payload<JsonValue>>(&msg.payload)
+                    .is_ok_and(|payload| payload.nonce == nonce)
+            })?;
+        }
+
+        trace!("Closing connection");
+        connection.send(&Message::new(
+            OpCode::Close,
+            serde_json::json!({ "code": 1000, "message": "Client shut down" }),
+        )?)?;
+
+        // Discord acknowledges with a close frame of its own
+        self.receive_until(connection, deadline, |_| false)
+    }
+
+    /// Receives messages until one matches `done`, Discord closes the connection, or `deadline` passes
+    ///
+    /// Any other message is handled as usual.
+    fn receive_until<F>(
+        &self,
+        connection: &mut Box<dyn Transport>,
+        deadline: Instant,
+        done: F,
+    ) -> Result<()>
+    where
+        F: Fn(&Message) -> bool,
+    {
+        while Instant::now() < deadline {
+            match connection.recv() {
+                Ok(msg) if msg.opcode == OpCode::Close => return Ok(()),
+                Ok(msg) if done(&msg) => return Ok(()),
+                Ok(msg) => {
+                    if msg.opcode == OpCode::Frame {
+                        self.handle_message(msg)?;
+                    }
+                }
+                Err(why) if why.io_would_block() => thread::sleep(time::Duration::from_millis(10)),
+                Err(DiscordError::ConnectionClosed) => return Ok(()),
+                Err(why) => return Err(why),
+            }
+        }
+
+        Err(RecvTimeoutError::Timeout.into())
+    }
+
     fn disconnect(&mut self) {
         self.handshake_completed = false;
+        *self.state.slot.lock() = None;
+        // Dropping the senders fails any command still waiting for a response
+        self.state.pending.lock().clear();
+        self.set_connection_state(ConnectionState::Disconnected);
         self.connection = Arc::new(None);
     }
 }
 
+/// The nonce of a command, to match it with its response
+fn nonce(msg: &Message) -> Option<String> {
+    serde_json::from_str::<Payload<JsonValue>>(&msg.payload)
+        .ok()
+        .and_then(|payload| payload.nonce)
+}
+
 fn send_and_receive_loop(manager: &mut Manager, rx: &Receiver<()>) {
     trace!("Starting sender loop");
 
-    let mut inbound = manager.inbound.1.clone();
     let outbound = manager.outbound.0.clone();
+    let mut failed_attempts = 0;
 
     loop {
-        if rx.try_recv().is_ok() {
+        // Stop once the client is shut down, or every handle to it is dropped
+        if rx.try_recv() != Err(TryRecvError::Empty) {
+            manager.set_connection_state(ConnectionState::ShuttingDown);
+            manager.close_gracefully(&outbound);
             break;
         }
 
@@ -117,14 +414,14 @@ fn send_and_receive_loop(manager: &mut Manager, rx: &Receiver<()>) {
         match *connection {
             Some(ref conn) => {
                 let mut connection = conn.lock();
-                match send_and_receive(
-                    &mut connection,
-                    &manager.event_handler_registry,
-                    &mut inbound,
-                    &outbound,
-                ) {
+                match send_and_receive(&mut connection, manager, &outbound) {
                     Err(DiscordError::IoError(ref err)) if err.kind() == ErrorKind::WouldBlock => {}
-                    Err(DiscordError::IoError(_) | DiscordError::ConnectionClosed) => {
+                    // The stream cannot be read past an oversized frame
+                    Err(
+                        DiscordError::IoError(_)
+                        | DiscordError::ConnectionClosed
+                        | DiscordError::FrameTooLarge(_),
+                    ) => {
                         manager.disconnect();
                     }
                     Err(DiscordError::TimeoutError(_)) => continue,
@@ -134,58 +431,208 @@ fn send_and_receive_loop(manager: &mut Manager, rx: &Receiver<()>) {
 
                 thread::sleep(time::Duration::from_millis(500));
             }
-            None => match manager.connect() {
-                Err(err) => {
-                    manager.event_handler_registry.handle(
-                        Event::Error,
-                        crate::models::EventData::Error(ErrorEvent {
-                            code: None,
-                            message: Some(err.to_string()),
-                        }),
-                    );
-
-                    if err.should_break() {
+            None => {
+                if let Err(err) = manager.connect() {
+                    manager.set_connection_state(ConnectionState::Disconnected);
+                    failed_attempts += 1;
+
+                    // Only report the first failure, rather than every retry
+                    if failed_attempts == 1 {
+                        manager.event_handler_registry.handle(
+                            Event::Error,
+                            EventData::Error(ErrorEvent {
+                                code: None,
+                                message: Some(err.to_string()),
+                            }),
+                        );
+                        error!("Failed to connect: {:?}", err);
+                    } else {
+                        trace!("Failed to reconnect: {:?}", err);
+                    }
+
+                    if let Some(delay) = manager.reconnect_policy.delay(failed_attempts) {
+                        trace!("Retrying connection in {:?}", delay);
+
+                        // Wait for the delay, unless the client is shut down or dropped in the meantime
+                        if rx.recv_timeout(delay) != Err(RecvTimeoutError::Timeout) {
+                            manager.set_connection_state(ConnectionState::ShuttingDown);
+                            break;
+                        }
+                    } else {
+                        error!("Giving up connecting after {} attempts", failed_attempts);
+                        manager.event_handler_registry.handle(
+                            Event::ReconnectFailed,
+                            EventData::ReconnectFailed(ReconnectFailedEvent {
+                                attempts: failed_attempts,
+                                message: err.to_string(),
+                            }),
+                        );
                         break;
                     }
-                    error!("Failed to connect: {:?}", err);
+                } else {
+                    manager.handshake_completed = true;
+                    failed_attempts = 0;
                 }
-                _ => manager.handshake_completed = true,
-            },
+            }
         }
     }
+
+    manager.disconnect();
+    manager.event_handler_registry.close_streams();
 }
 
 fn send_and_receive(
-    connection: &mut Socket,
-    event_handler_registry: &Arc<HandlerRegistry>,
-    inbound: &mut Tx,
+    connection: &mut Box<dyn Transport>,
+    manager: &Manager,
     outbound: &Rx,
 ) -> Result<()> {
     while let Ok(msg) = outbound.try_recv() {
         trace!("Sending message");
-        connection.send(&msg)?;
+        match connection.send(&msg) {
+            // Nothing was sent, so only the command fails, not the connection
+            Err(why @ DiscordError::TranscriptMismatch { .. }) => manager.fail(&msg, why),
+            result => result?,
+        }
         trace!("Sent message");
     }
 
-    trace!("Receiving from connection");
-    let msg = connection.recv()?;
-    trace!("Received from connection");
+    // Handle every frame already received, rather than one per iteration of the loop
+    loop {
+        trace!("Receiving from connection");
+        let msg = connection.recv()?;
+        trace!("Received from connection");
 
-    let payload: Payload<JsonValue> = serde_json::from_str(&msg.payload)?;
+        if let Err(why) = manager.handle_message(msg) {
+            error!("Failed to handle message: {}", why);
+        }
+    }
+}
 
-    trace!("Received payload");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ConnectionStateEvent;
+
+    #[test]
+    fn fires_connection_state_events() {
+        let registry = Arc::new(HandlerRegistry::default());
+        let manager = Manager::new(
+            0,
+            ConnectionConfig::default(),
+            ReconnectPolicy::default(),
+            true,
+            registry.clone(),
+        );
+
+        let (tx, rx) = unbounded();
+        let _changed = registry.register(Event::ConnectionStateChanged, move |ctx| {
+            tx.send(ctx.event).unwrap();
+        });
+
+        manager.set_connection_state(ConnectionState::Connecting);
+
+        assert_eq!(manager.connection_state(), ConnectionState::Connecting);
+        assert_eq!(
+            rx.recv_timeout(time::Duration::from_secs(1)).unwrap(),
+            EventData::ConnectionState(ConnectionStateEvent {
+                previous: ConnectionState::Disconnected,
+                current: ConnectionState::Connecting,
+            })
+        );
+
+        // Staying in the same state is not a transition
+        manager.set_connection_state(ConnectionState::Connecting);
+        assert!(rx.recv_timeout(time::Duration::from_millis(100)).is_err());
+    }
 
-    if let Payload {
-        evt: Some(event), ..
-    } = &payload
-    {
-        trace!("Got event");
-        let event_data = event.parse_data(into_error!(payload.data.clone())?);
-        event_handler_registry.handle(*event, event_data);
-    } else {
-        trace!("Got message");
-        inbound.send(msg)?;
+    fn frame(payload: &JsonValue) -> Message {
+        Message::new(crate::models::OpCode::Frame, payload).unwrap()
     }
 
-    Ok(())
+    #[test]
+    fn routes_responses_by_nonce() {
+        let registry = Arc::new(HandlerRegistry::default());
+        let manager = Manager::new(
+            0,
+            ConnectionConfig::default(),
+            ReconnectPolicy::default(),
+            true,
+            registry.clone(),
+        );
+
+        let first = manager.request("first", frame(&JsonValue::Null)).unwrap();
+        let second = manager.request("second", frame(&JsonValue::Null)).unwrap();
+
+        let (tx, events) = unbounded();
+        let _join = registry.register(Event::ActivityJoin, move |ctx| {
+            tx.send(ctx.event).unwrap();
+        });
+
+        let second_response = frame(&serde_json::json!({
+            "cmd": "SET_ACTIVITY", "data": {}, "nonce": "second"
+        }));
+        let first_response = frame(&serde_json::json!({
+            "cmd": "SET_ACTIVITY", "data": {}, "nonce": "first"
+        }));
+        let unknown_response = frame(&serde_json::json!({
+            "cmd": "SET_ACTIVITY", "data": {}, "nonce": "unknown"
+        }));
+        let event = frame(&serde_json::json!({
+            "cmd": "DISPATCH", "data": { "secret": "shh" }, "evt": "ACTIVITY_JOIN"
+        }));
+
+        // Responses can arrive in any order, and are interleaved with events
+        manager.handle_message(second_response.clone()).unwrap();
+        manager.handle_message(event).unwrap();
+        manager.handle_message(unknown_response).unwrap();
+        manager.handle_message(first_response.clone()).unwrap();
+
+        assert_eq!(first.try_recv().unwrap().unwrap(), first_response);
+        assert_eq!(second.try_recv().unwrap().unwrap(), second_response);
+        assert!(first.try_recv().is_err());
+        assert!(manager.state.pending.lock().is_empty());
+        assert!(matches!(
+            events.recv_timeout(time::Duration::from_secs(1)).unwrap(),
+            EventData::ActivityJoin(_)
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn retries_refused_connections_until_policy_gives_up() {
+        let dir = std::env::temp_dir().join(format!("discord-presence-{}", crate::utils::nonce()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("discord-ipc-0");
+        // A stale socket left behind by Discord crashing, refusing connections
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let registry = Arc::new(HandlerRegistry::default());
+        let mut manager = Manager::new(
+            0,
+            ConnectionConfig {
+                ipc_path: Some(path),
+                ..ConnectionConfig::default()
+            },
+            ReconnectPolicy::Fixed {
+                interval: time::Duration::from_millis(10),
+                max_attempts: Some(3),
+            },
+            true,
+            registry.clone(),
+        );
+
+        let (tx, failed) = unbounded();
+        let _failed = registry.register(Event::ReconnectFailed, move |ctx| {
+            tx.send(ctx.event).unwrap();
+        });
+
+        let (_stop, rx) = bounded(1);
+        manager.start(rx).join().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(
+            failed.recv_timeout(time::Duration::from_secs(5)).unwrap(),
+            EventData::ReconnectFailed(ReconnectFailedEvent { attempts: 3, .. })
+        ));
+    }
 }
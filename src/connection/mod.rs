@@ -1,8 +1,23 @@
 mod base;
 mod manager;
+mod reconnect;
+mod session;
+mod state;
+mod stream;
+mod transcript;
+#[cfg(feature = "websocket")]
+mod websocket;
 
-pub use base::Connection;
+#[cfg(feature = "async")]
+pub use base::handshake_message;
+pub use base::{Connection, ConnectionConfig, Connector, Transport, DEFAULT_SHUTDOWN_TIMEOUT};
 pub use manager::Manager;
+pub use reconnect::ReconnectPolicy;
+pub use state::ConnectionState;
+pub use stream::StreamTransport;
+pub use transcript::{MessageDirection, Recorder, Replay, Transcript, TranscriptEntry};
+#[cfg(feature = "websocket")]
+pub use websocket::{WebSocketTransport, WEBSOCKET_PORTS};
 
 cfg_if::cfg_if! {
     if #[cfg(unix)] {
@@ -0,0 +1,136 @@
+use crate::utils;
+use std::{convert::TryFrom, time::Duration};
+
+/// How the client retries connecting to Discord, e.g. when it is not running, or after it restarts
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use discord_presence::{Client, ReconnectPolicy};
+/// let mut drpc = Client::builder(1003450375732482138)
+///     .reconnect_policy(ReconnectPolicy::Fixed {
+///         interval: Duration::from_secs(5),
+///         max_attempts: Some(10),
+///     })
+///     .build();
+/// ```
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum ReconnectPolicy {
+    /// Give up after the first failed attempt
+    Never,
+    /// Wait the same interval between each attempt
+    Fixed {
+        /// Time to wait between attempts
+        interval: Duration,
+        /// Give up after this many consecutive failed attempts, or never if `None`
+        max_attempts: Option<u32>,
+    },
+    /// Double the wait after each failed attempt, up to `max`, with some random jitter
+    Exponential {
+        /// Time to wait after the first failed attempt
+        initial: Duration,
+        /// Longest time to wait between attempts
+        max: Duration,
+        /// Give up after this many consecutive failed attempts, or never if `None`
+        max_attempts: Option<u32>,
+    },
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self::Exponential {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(30),
+            max_attempts: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    #[must_use]
+    /// How long to wait after the given number of consecutive failed attempts,
+    /// or `None` if the client should give up
+    pub fn delay(&self, failed_attempts: u32) -> Option<Duration> {
+        match *self {
+            Self::Never => None,
+            Self::Fixed {
+                interval,
+                max_attempts,
+            } => {
+                if exhausted(failed_attempts, max_attempts) {
+                    None
+                } else {
+                    Some(interval)
+                }
+            }
+            Self::Exponential {
+                initial,
+                max,
+                max_attempts,
+            } => {
+                if exhausted(failed_attempts, max_attempts) {
+                    return None;
+                }
+
+                let factor = 2u32.saturating_pow(failed_attempts.saturating_sub(1));
+                let delay = initial.saturating_mul(factor).min(max);
+
+                // Wait at least half the delay, so that clients started together spread out
+                // without ever retrying immediately
+                let half = delay / 2;
+                let jitter = u64::try_from(half.as_millis()).unwrap_or(u64::MAX);
+
+                Some(half + Duration::from_millis(utils::random_below(jitter + 1)))
+            }
+        }
+    }
+}
+
+fn exhausted(failed_attempts: u32, max_attempts: Option<u32>) -> bool {
+    max_attempts.is_some_and(|max| failed_attempts >= max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_reconnects() {
+        assert_eq!(ReconnectPolicy::Never.delay(1), None);
+    }
+
+    #[test]
+    fn fixed_gives_up_after_max_attempts() {
+        let policy = ReconnectPolicy::Fixed {
+            interval: Duration::from_secs(5),
+            max_attempts: Some(3),
+        };
+
+        assert_eq!(policy.delay(1), Some(Duration::from_secs(5)));
+        assert_eq!(policy.delay(2), Some(Duration::from_secs(5)));
+        assert_eq!(policy.delay(3), None);
+    }
+
+    #[test]
+    fn exponential_doubles_up_to_max() {
+        let policy = ReconnectPolicy::Exponential {
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(10),
+            max_attempts: None,
+        };
+
+        for (attempt, expected) in [(1, 1), (2, 2), (3, 4), (4, 8), (5, 10), (40, 10)] {
+            let delay = policy.delay(attempt).unwrap();
+            let expected = Duration::from_secs(expected);
+
+            assert!(
+                delay >= expected / 2,
+                "{:?} too short for {}",
+                delay,
+                attempt
+            );
+            assert!(delay <= expected, "{:?} too long for {}", delay, attempt);
+        }
+    }
+}
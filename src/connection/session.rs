@@ -0,0 +1,140 @@
+use crate::{
+    models::{
+        commands::{AuthenticateArgs, SubscriptionArgs},
+        payload::Payload,
+        rich_presence::SetActivityArgs,
+        Command, Event, Message, OpCode,
+    },
+    Result,
+};
+
+/// The state set on Discord by the client, re-applied after reconnecting
+#[derive(Debug, Default)]
+pub struct Session {
+    /// The token the client last authenticated with, needed again before the subscriptions that require auth
+    access_token: Option<String>,
+    activity: Option<SetActivityArgs>,
+    subscriptions: Vec<(Event, SubscriptionArgs)>,
+}
+
+impl Session {
+    pub fn authenticate(&mut self, access_token: String) {
+        self.access_token = Some(access_token);
+    }
+
+    pub fn set_activity(&mut self, args: SetActivityArgs) {
+        self.activity = Some(args);
+    }
+
+    pub fn clear_activity(&mut self) {
+        self.activity = None;
+    }
+
+    pub fn subscribe(&mut self, event: Event, args: SubscriptionArgs) {
+        let subscription = (event, args);
+
+        if !self.subscriptions.contains(&subscription) {
+            self.subscriptions.push(subscription);
+        }
+    }
+
+    pub fn unsubscribe(&mut self, event: Event, args: &SubscriptionArgs) {
+        self.subscriptions
+            .retain(|(evt, sub_args)| !(*evt == event && sub_args == args));
+    }
+
+    /// The commands to send to restore this session on a new connection
+    pub fn messages(&self) -> Result<Vec<Message>> {
+        let mut messages = Vec::with_capacity(self.subscriptions.len() + 2);
+
+        // First, as Discord handles commands in order and most subscriptions require auth
+        if let Some(access_token) = &self.access_token {
+            messages.push(Message::new(
+                OpCode::Frame,
+                Payload::with_nonce(
+                    Command::Authenticate,
+                    Some(AuthenticateArgs::new(access_token.as_str())),
+                    None,
+                    None,
+                ),
+            )?);
+        }
+
+        if let Some(activity) = &self.activity {
+            messages.push(Message::new(
+                OpCode::Frame,
+                Payload::with_nonce(Command::SetActivity, Some(activity), None, None),
+            )?);
+        }
+
+        for (event, args) in &self.subscriptions {
+            messages.push(Message::new(
+                OpCode::Frame,
+                Payload::with_nonce(Command::Subscribe, Some(args), None, Some(*event)),
+            )?);
+        }
+
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value as JsonValue;
+
+    fn commands(session: &Session) -> Vec<(Command, Option<Event>)> {
+        session
+            .messages()
+            .unwrap()
+            .into_iter()
+            .map(|message| {
+                let payload: Payload<JsonValue> = serde_json::from_str(&message.payload).unwrap();
+                (payload.cmd, payload.evt)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn restores_activity_and_subscriptions() {
+        let mut session = Session::default();
+        assert!(commands(&session).is_empty());
+
+        session.set_activity(SetActivityArgs::new(|a| a.state("rusting")));
+        session.subscribe(Event::ActivityJoin, SubscriptionArgs::new());
+        session.subscribe(Event::ActivityJoin, SubscriptionArgs::new());
+        session.subscribe(Event::ActivitySpectate, SubscriptionArgs::new());
+
+        assert_eq!(
+            commands(&session),
+            vec![
+                (Command::SetActivity, None),
+                (Command::Subscribe, Some(Event::ActivityJoin)),
+                (Command::Subscribe, Some(Event::ActivitySpectate)),
+            ]
+        );
+
+        session.clear_activity();
+        session.unsubscribe(Event::ActivityJoin, &SubscriptionArgs::new());
+
+        assert_eq!(
+            commands(&session),
+            vec![(Command::Subscribe, Some(Event::ActivitySpectate))]
+        );
+    }
+
+    #[test]
+    fn authenticates_before_restoring_subscriptions() {
+        let mut session = Session::default();
+        session.subscribe(Event::GuildStatus, SubscriptionArgs::new());
+        session.authenticate("token".to_owned());
+
+        assert_eq!(
+            commands(&session),
+            vec![
+                (Command::Authenticate, None),
+                (Command::Subscribe, Some(Event::GuildStatus)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,27 @@
+/// The state of a client's connection to Discord
+///
+/// The client moves through these states in order,
+/// falling back to [`ConnectionState::Disconnected`] whenever connecting fails or the connection is lost.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Copy, Clone, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ConnectionState {
+    /// Not connected to Discord, e.g. because it is not running
+    #[default]
+    Disconnected,
+    /// Looking for, and connecting to, the Discord socket
+    Connecting,
+    /// Connected to the socket, waiting for Discord to accept the handshake
+    Handshaking,
+    /// Connected to Discord, and ready to send commands
+    Connected,
+    /// The client is shutting down, and will not reconnect
+    ShuttingDown,
+}
+
+impl ConnectionState {
+    #[must_use]
+    /// Whether commands can currently be sent to Discord
+    pub fn is_connected(self) -> bool {
+        self == Self::Connected
+    }
+}
@@ -0,0 +1,111 @@
+use super::base::{read_frame, write_frame, Transport};
+use crate::{
+    error::{DiscordError, Result},
+    models::message::Message,
+};
+use bytes::BytesMut;
+use std::io::{self, ErrorKind, Read, Write};
+
+/// A [`Transport`] over any [`Read`] + [`Write`] stream, e.g. a TCP bridge to another machine or an in-process pipe
+///
+/// The stream should be non-blocking, or have a short read timeout,
+/// as the client only sends its commands between reads.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use discord_presence::{Client, StreamTransport};
+/// # use std::net::TcpStream;
+/// let mut drpc = Client::builder(1003450375732482138)
+///     .connector(|| {
+///         let stream = TcpStream::connect("192.168.1.2:6000")?;
+///         stream.set_nonblocking(true)?;
+///         Ok(StreamTransport::new(stream))
+///     })
+///     .build();
+/// drpc.start();
+/// ```
+#[derive(Debug)]
+pub struct StreamTransport<S> {
+    stream: S,
+    read_buffer: BytesMut,
+}
+
+impl<S> StreamTransport<S>
+where
+    S: Read + Write + Send,
+{
+    /// Create a new `StreamTransport` over `stream`
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            read_buffer: BytesMut::new(),
+        }
+    }
+
+    /// The underlying stream
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// The underlying stream, to write or read from directly
+    ///
+    /// Reading from it directly loses any partially received message.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Returns the underlying stream
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S> Transport for StreamTransport<S>
+where
+    S: Read + Write + Send,
+{
+    fn send(&mut self, message: &Message) -> Result<()> {
+        write_frame(&mut self.stream, message)
+    }
+
+    fn recv(&mut self) -> Result<Message> {
+        match read_frame(&mut self.stream, &mut self.read_buffer) {
+            // Read timeouts are reported as `TimedOut` on Windows
+            Err(DiscordError::IoError(why)) if why.kind() == ErrorKind::TimedOut => {
+                Err(io::Error::from(ErrorKind::WouldBlock).into())
+            }
+            result => result,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::models::OpCode;
+    use std::{net::Shutdown, os::unix::net::UnixStream};
+
+    #[test]
+    fn sends_and_receives_frames() {
+        let (stream, discord) = UnixStream::pair().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut transport = StreamTransport::new(stream);
+        let mut discord = StreamTransport::new(discord);
+
+        let message = Message::new(OpCode::Frame, "ready").unwrap();
+        assert!(transport.recv().unwrap_err().io_would_block());
+
+        discord.send(&message).unwrap();
+        assert_eq!(transport.recv().unwrap(), message);
+
+        transport.send(&message).unwrap();
+        assert_eq!(discord.recv().unwrap(), message);
+
+        discord.get_ref().shutdown(Shutdown::Both).unwrap();
+        assert!(matches!(
+            transport.recv(),
+            Err(DiscordError::ConnectionClosed)
+        ));
+    }
+}
@@ -0,0 +1,490 @@
+use super::base::Transport;
+use crate::{
+    error::{DiscordError, Result},
+    models::message::{Message, OpCode},
+};
+use parking_lot::Mutex;
+use serde_json::Value as JsonValue;
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+    fs::{File, OpenOptions},
+    io::{self, BufRead, BufReader, ErrorKind, LineWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Replaces secrets in recorded payloads
+const REDACTED: &str = "[redacted]";
+
+/// Which way a message in a transcript went
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Copy, Clone, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageDirection {
+    /// Sent to Discord
+    Sent,
+    /// Received from Discord
+    Received,
+}
+
+/// A line of a transcript, describing a single message
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
+pub struct TranscriptEntry {
+    /// Which way the message went
+    pub direction: MessageDirection,
+    /// The opcode of the message
+    pub opcode: OpCode,
+    /// The payload of the message, as JSON if it could be parsed, or as a string otherwise
+    pub payload: JsonValue,
+    /// When the message went, in milliseconds since the Unix epoch
+    pub timestamp: u64,
+}
+
+impl TranscriptEntry {
+    fn new(direction: MessageDirection, message: &Message) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| {
+                u64::try_from(since.as_millis()).unwrap_or(u64::MAX)
+            });
+
+        let mut payload = serde_json::from_str(&message.payload)
+            .unwrap_or_else(|_| JsonValue::String(message.payload.clone()));
+        redact(&mut payload);
+
+        Self {
+            direction,
+            opcode: message.opcode,
+            payload,
+            timestamp,
+        }
+    }
+
+    fn message(&self) -> Result<Message> {
+        Ok(Message {
+            opcode: self.opcode,
+            payload: match &self.payload {
+                JsonValue::String(payload) => payload.clone(),
+                payload => serde_json::to_string(payload)?,
+            },
+        })
+    }
+
+    fn nonce(&self) -> Option<&str> {
+        self.payload.get("nonce").and_then(JsonValue::as_str)
+    }
+
+    /// Whether `message` was sent in place of this recorded message, ignoring args that change between runs
+    fn matches(&self, message: &Message) -> bool {
+        let sent: JsonValue = serde_json::from_str(&message.payload).unwrap_or_default();
+
+        self.direction == MessageDirection::Sent
+            && self.opcode == message.opcode
+            && self.payload.get("cmd") == sent.get("cmd")
+            && self.payload.get("evt") == sent.get("evt")
+    }
+}
+
+/// Replaces the authorization codes and tokens in a payload, so transcripts can be shared safely
+fn redact(payload: &mut JsonValue) {
+    match payload {
+        JsonValue::Object(fields) => {
+            for (key, value) in fields {
+                // Error codes are numbers, and kept
+                let secret = key == "code" || key == "token" || key.ends_with("_token");
+
+                if secret && value.is_string() {
+                    *value = JsonValue::String(REDACTED.to_owned());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        JsonValue::Array(values) => values.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// A JSON-lines file every message exchanged with Discord is appended to, see [`ClientBuilder::record_transcript`](crate::ClientBuilder::record_transcript)
+///
+/// The file is created on the first message, and shared by every connection of the client.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    path: PathBuf,
+    writer: Arc<Mutex<Option<LineWriter<File>>>>,
+}
+
+impl Transcript {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            writer: Arc::default(),
+        }
+    }
+
+    /// Appends a message to the transcript
+    fn record(&self, direction: MessageDirection, message: &Message) -> Result<()> {
+        let mut writer = self.writer.lock();
+
+        let writer = match &mut *writer {
+            Some(writer) => writer,
+            None => writer.insert(LineWriter::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.path)?,
+            )),
+        };
+
+        serde_json::to_writer(&mut *writer, &TranscriptEntry::new(direction, message))?;
+        writer.write_all(b"\n")?;
+
+        Ok(())
+    }
+}
+
+/// Records every message going through another [`Transport`] to a [`Transcript`]
+pub struct Recorder {
+    inner: Box<dyn Transport>,
+    transcript: Transcript,
+}
+
+impl Recorder {
+    pub fn new(inner: Box<dyn Transport>, transcript: Transcript) -> Self {
+        Self { inner, transcript }
+    }
+
+    fn record(&self, direction: MessageDirection, message: &Message) {
+        // A broken transcript should not break the client
+        if let Err(why) = self.transcript.record(direction, message) {
+            error!("Failed to record message: {}", why);
+        }
+    }
+}
+
+impl Transport for Recorder {
+    fn send(&mut self, message: &Message) -> Result<()> {
+        self.inner.send(message)?;
+        self.record(MessageDirection::Sent, message);
+
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Message> {
+        let message = self.inner.recv()?;
+        self.record(MessageDirection::Received, &message);
+
+        Ok(message)
+    }
+
+    fn slot(&self) -> Option<u8> {
+        self.inner.slot()
+    }
+}
+
+#[derive(Debug, Default)]
+struct ReplayState {
+    entries: VecDeque<TranscriptEntry>,
+    /// The nonces of the recorded commands, mapped to those of the commands sent while replaying
+    nonces: HashMap<String, String>,
+}
+
+/// A recorded [`Transcript`], played back in place of Discord, see [`ClientBuilder::replay_transcript`](crate::ClientBuilder::replay_transcript)
+///
+/// Received messages are played back as soon as the messages recorded before them were sent,
+/// regardless of their timestamps, with their nonces replaced by those of the commands sent now.
+/// Sending a message other than the one recorded next fails with [`DiscordError::TranscriptMismatch`].
+/// Once the transcript ends, nothing is received anymore.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    path: PathBuf,
+    /// Loaded on the first connection, and shared by the following ones
+    state: Arc<Mutex<Option<ReplayState>>>,
+}
+
+impl Replay {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            state: Arc::default(),
+        }
+    }
+
+    /// Starts playing back a connection, from where the previous connection stopped
+    pub fn connect(&self) -> Result<ReplayConnection> {
+        let mut state = self.state.lock();
+
+        if state.is_none() {
+            let mut entries = VecDeque::new();
+
+            for line in BufReader::new(File::open(&self.path)?).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    entries.push_back(serde_json::from_str(&line)?);
+                }
+            }
+
+            *state = Some(ReplayState {
+                entries,
+                nonces: HashMap::new(),
+            });
+        }
+
+        Ok(ReplayConnection {
+            state: self.state.clone(),
+            handshaking: true,
+        })
+    }
+}
+
+/// A single connection of a [`Replay`]
+pub struct ReplayConnection {
+    state: Arc<Mutex<Option<ReplayState>>>,
+    /// Until the handshake is sent, as a recorded handshake means the recorded connection was closed
+    handshaking: bool,
+}
+
+impl Transport for ReplayConnection {
+    fn send(&mut self, message: &Message) -> Result<()> {
+        let mut state = self.state.lock();
+        let state = into_error!(state.as_mut())?;
+        self.handshaking = false;
+
+        // Messages from Discord recorded before it may not have been received yet, and stay queued
+        let next = state
+            .entries
+            .iter()
+            .position(|entry| entry.direction == MessageDirection::Sent);
+
+        let Some(index) = next.filter(|&index| state.entries[index].matches(message)) else {
+            return Err(DiscordError::TranscriptMismatch {
+                expected: next.map_or_else(
+                    || "the end of the transcript".to_owned(),
+                    |index| {
+                        let recorded = &state.entries[index];
+                        format!("{:?} {}", recorded.opcode, recorded.payload)
+                    },
+                ),
+                actual: format!("{:?} {}", message.opcode, message.payload),
+            });
+        };
+
+        let recorded = into_error!(state.entries.remove(index))?;
+        let sent: JsonValue = serde_json::from_str(&message.payload).unwrap_or_default();
+        if let (Some(old), Some(new)) = (
+            recorded.nonce(),
+            sent.get("nonce").and_then(JsonValue::as_str),
+        ) {
+            state.nonces.insert(old.to_owned(), new.to_owned());
+        }
+
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Message> {
+        let mut state = self.state.lock();
+        let state = into_error!(state.as_mut())?;
+
+        match state.entries.front() {
+            Some(entry) if entry.direction == MessageDirection::Received => {}
+            // The recorded connection was closed, and the client reconnected
+            Some(entry) if entry.opcode == OpCode::Handshake && !self.handshaking => {
+                return Err(DiscordError::ConnectionClosed);
+            }
+            // Waiting for the client to send what was recorded next, or the transcript ended
+            _ => return Err(io::Error::from(ErrorKind::WouldBlock).into()),
+        }
+
+        let mut entry = into_error!(state.entries.pop_front())?;
+
+        let nonce = entry
+            .nonce()
+            .and_then(|nonce| state.nonces.get(nonce))
+            .cloned();
+        if let (Some(nonce), JsonValue::Object(payload)) = (nonce, &mut entry.payload) {
+            payload.insert("nonce".to_owned(), JsonValue::String(nonce));
+        }
+
+        entry.message()
+    }
+
+    fn slot(&self) -> Option<u8> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use serde_json::json;
+    use std::{env, fs};
+
+    fn frame(payload: &JsonValue) -> Message {
+        Message::new(OpCode::Frame, payload).unwrap()
+    }
+
+    fn entry(direction: MessageDirection, opcode: OpCode, payload: JsonValue) -> String {
+        serde_json::to_string(&TranscriptEntry {
+            direction,
+            opcode,
+            payload,
+            timestamp: 0,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn records_and_replays_with_new_nonces() {
+        let dir = env::temp_dir().join(format!("discord-presence-{}", utils::nonce()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("transcript.jsonl");
+
+        let lines = [
+            entry(MessageDirection::Sent, OpCode::Handshake, json!({ "v": 1 })),
+            entry(
+                MessageDirection::Received,
+                OpCode::Frame,
+                json!({ "evt": "READY" }),
+            ),
+            entry(
+                MessageDirection::Sent,
+                OpCode::Frame,
+                json!({ "cmd": "SET_ACTIVITY", "nonce": "old" }),
+            ),
+            entry(
+                MessageDirection::Received,
+                OpCode::Frame,
+                json!({ "cmd": "SET_ACTIVITY", "nonce": "old" }),
+            ),
+            entry(MessageDirection::Sent, OpCode::Handshake, json!({ "v": 1 })),
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let recorded = dir.join("recorded.jsonl");
+        let replay = Replay::new(&path);
+        let mut connection = Recorder::new(
+            Box::new(replay.connect().unwrap()),
+            Transcript::new(&recorded),
+        );
+
+        assert_eq!(
+            connection.handshake(1).unwrap(),
+            frame(&json!({ "evt": "READY" }))
+        );
+        // Nothing is received until the command recorded next is sent
+        assert!(connection.recv().unwrap_err().io_would_block());
+
+        connection
+            .send(&frame(&json!({ "cmd": "SET_ACTIVITY", "nonce": "new" })))
+            .unwrap();
+        assert_eq!(
+            connection.recv().unwrap(),
+            frame(&json!({ "cmd": "SET_ACTIVITY", "nonce": "new" }))
+        );
+
+        // The recorded connection was closed here, and continues with the next one
+        assert!(matches!(
+            connection.recv(),
+            Err(DiscordError::ConnectionClosed)
+        ));
+        let mut reconnected = replay.connect().unwrap();
+        reconnected.send(&handshake(1)).unwrap();
+        assert!(reconnected.recv().unwrap_err().io_would_block());
+
+        let transcript: Vec<TranscriptEntry> = fs::read_to_string(&recorded)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            transcript
+                .iter()
+                .map(|entry| (entry.direction, entry.opcode))
+                .collect::<Vec<_>>(),
+            vec![
+                (MessageDirection::Sent, OpCode::Handshake),
+                (MessageDirection::Received, OpCode::Frame),
+                (MessageDirection::Sent, OpCode::Frame),
+                (MessageDirection::Received, OpCode::Frame),
+            ]
+        );
+        assert_eq!(transcript[2].payload["nonce"], "new");
+    }
+
+    #[test]
+    fn redacts_codes_and_tokens() {
+        let authorize = TranscriptEntry::new(
+            MessageDirection::Received,
+            &frame(&json!({ "cmd": "AUTHORIZE", "data": { "code": "secret" }, "nonce": "1" })),
+        );
+        let authenticate = TranscriptEntry::new(
+            MessageDirection::Sent,
+            &frame(&json!({ "cmd": "AUTHENTICATE", "args": { "access_token": "secret" } })),
+        );
+        let nested = TranscriptEntry::new(
+            MessageDirection::Received,
+            &frame(
+                &json!({ "data": [{ "token": "secret", "rpc_token": "secret", "code": 4000 }] }),
+            ),
+        );
+
+        assert_eq!(authorize.payload["data"]["code"], REDACTED);
+        assert_eq!(authorize.payload["nonce"], "1");
+        assert_eq!(authenticate.payload["args"]["access_token"], REDACTED);
+        assert_eq!(nested.payload["data"][0]["token"], REDACTED);
+        assert_eq!(nested.payload["data"][0]["rpc_token"], REDACTED);
+        // Error codes are not secret
+        assert_eq!(nested.payload["data"][0]["code"], 4000);
+    }
+
+    #[test]
+    fn fails_messages_that_were_not_recorded() {
+        let path = env::temp_dir().join(format!("discord-presence-{}.jsonl", utils::nonce()));
+        let lines = [
+            entry(MessageDirection::Sent, OpCode::Handshake, json!({ "v": 1 })),
+            entry(
+                MessageDirection::Received,
+                OpCode::Frame,
+                json!({ "evt": "READY" }),
+            ),
+            entry(
+                MessageDirection::Sent,
+                OpCode::Frame,
+                json!({ "cmd": "SET_ACTIVITY", "nonce": "old" }),
+            ),
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let mut connection = Replay::new(&path).connect().unwrap();
+        fs::remove_file(&path).unwrap();
+        connection.send(&handshake(1)).unwrap();
+
+        // Sent before the recorded `READY` is received, which stays queued
+        let result = connection.send(&frame(&json!({ "cmd": "GET_GUILDS", "nonce": "new" })));
+        let Err(DiscordError::TranscriptMismatch { expected, actual }) = result else {
+            panic!("expected a mismatch, got {:?}", result);
+        };
+        assert!(expected.contains("SET_ACTIVITY"), "{}", expected);
+        assert!(actual.contains("GET_GUILDS"), "{}", actual);
+
+        connection
+            .send(&frame(&json!({ "cmd": "SET_ACTIVITY", "nonce": "new" })))
+            .unwrap();
+        assert_eq!(
+            connection.recv().unwrap(),
+            frame(&json!({ "evt": "READY" }))
+        );
+        assert!(matches!(
+            connection.send(&frame(&json!({ "cmd": "SET_ACTIVITY", "nonce": "newer" }))),
+            Err(DiscordError::TranscriptMismatch { expected, .. }) if expected == "the end of the transcript"
+        ));
+    }
+
+    fn handshake(client_id: u64) -> Message {
+        super::super::base::handshake_message(client_id).unwrap()
+    }
+}
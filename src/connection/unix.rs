@@ -1,21 +1,62 @@
 use super::base::Connection;
 use crate::Result;
-use std::{env, net::Shutdown, os::unix::net::UnixStream, path::PathBuf, time};
+use bytes::BytesMut;
+use std::{
+    env,
+    net::Shutdown,
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    time,
+};
+
+/// Directories, relative to [`Socket::ipc_path`], that sandboxed Discord clients put their socket in.
+///
+/// Searched in order, after the base directory itself.
+const SANDBOX_DIRS: &[&str] = &[
+    // Flatpak
+    "app/com.discordapp.Discord",
+    "app/com.discordapp.DiscordCanary",
+    "app/com.discordapp.DiscordPTB",
+    // Snap
+    "snap.discord",
+    "snap.discord-canary",
+    // Vesktop (Flatpak), which ships its own arRPC server
+    "app/dev.vencord.Vesktop",
+    ".flatpak/dev.vencord.Vesktop/xdg-run",
+];
 
 pub struct Socket {
-    socket: UnixStream,
+    stream: UnixStream,
+    slot: Option<u8>,
+    read_buffer: BytesMut,
+}
+
+impl Socket {
+    /// All the directories searched for the socket, given the base directory.
+    fn search_dirs_in(base: &Path) -> Vec<PathBuf> {
+        std::iter::once(base.to_path_buf())
+            .chain(SANDBOX_DIRS.iter().map(|dir| base.join(dir)))
+            .collect()
+    }
 }
 
 impl Connection for Socket {
     type Socket = UnixStream;
 
-    fn connect() -> Result<Self> {
-        let connection_name = Self::socket_path(0);
-        let socket = UnixStream::connect(connection_name)?;
-        socket.set_nonblocking(true)?;
-        socket.set_write_timeout(Some(time::Duration::from_secs(30)))?;
-        socket.set_read_timeout(Some(time::Duration::from_secs(30)))?;
-        Ok(Self { socket })
+    fn connect_to(path: &Path, slot: Option<u8>) -> Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        stream.set_nonblocking(true)?;
+        stream.set_write_timeout(Some(time::Duration::from_secs(30)))?;
+        stream.set_read_timeout(Some(time::Duration::from_secs(30)))?;
+        Ok(Self {
+            stream,
+            slot,
+            read_buffer: BytesMut::new(),
+        })
+    }
+
+    fn slot(&self) -> Option<u8> {
+        self.slot
     }
 
     fn ipc_path() -> PathBuf {
@@ -29,15 +70,137 @@ impl Connection for Socket {
         PathBuf::from(tmp)
     }
 
+    fn search_dirs() -> Vec<PathBuf> {
+        Self::search_dirs_in(&Self::ipc_path())
+    }
+
     fn socket(&mut self) -> &mut Self::Socket {
-        &mut self.socket
+        &mut self.stream
+    }
+
+    fn read_buffer(&mut self) -> &mut BytesMut {
+        &mut self.read_buffer
     }
 }
 
 impl Drop for Socket {
     fn drop(&mut self) {
-        if self.socket.shutdown(Shutdown::Both).is_err() {
+        if self.stream.shutdown(Shutdown::Both).is_err() {
             error!("Failed to properly shut down socket");
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        connection::{base::candidates_in, ConnectionConfig},
+        models::{Message, OpCode},
+    };
+    use std::{fs, io::Write, os::unix::net::UnixListener};
+
+    fn temp_dir() -> PathBuf {
+        let dir = env::temp_dir().join(format!("discord-presence-{}", crate::utils::nonce()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn connects_to_first_available_slot() {
+        let dir = temp_dir();
+
+        // A stale socket left behind by a previous Discord instance, nobody is listening on it
+        drop(UnixListener::bind(dir.join("discord-ipc-0")).unwrap());
+        let _second = UnixListener::bind(dir.join("discord-ipc-2")).unwrap();
+        let _third = UnixListener::bind(dir.join("discord-ipc-3")).unwrap();
+
+        let candidates = candidates_in(&ConnectionConfig::default(), Socket::search_dirs_in(&dir));
+        let socket = Socket::connect_any(candidates);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            socket.expect("Failed to connect to any slot").slot(),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn searches_extra_dirs_first() {
+        let dir = temp_dir();
+        let _listener = UnixListener::bind(dir.join("discord-ipc-4")).unwrap();
+
+        let config = ConnectionConfig {
+            search_dirs: vec![dir.clone()],
+            ..ConnectionConfig::default()
+        };
+        let socket = Socket::connect(&config);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            socket.expect("Failed to connect to extra dir").slot(),
+            Some(4)
+        );
+    }
+
+    #[test]
+    fn connects_to_exact_ipc_path() {
+        let dir = temp_dir();
+        let _fake = UnixListener::bind(dir.join("fake-discord.sock")).unwrap();
+        let _slot = UnixListener::bind(dir.join("discord-ipc-0")).unwrap();
+
+        let config = ConnectionConfig {
+            ipc_path: Some(dir.join("fake-discord.sock")),
+            search_dirs: vec![dir.clone()],
+            ..ConnectionConfig::default()
+        };
+        let socket = Socket::connect(&config);
+
+        let missing = ConnectionConfig {
+            ipc_path: Some(dir.join("missing.sock")),
+            search_dirs: vec![dir.clone()],
+            ..ConnectionConfig::default()
+        };
+        let missing_socket = Socket::connect(&missing);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(socket.expect("Failed to connect to IPC path").slot(), None);
+        // Never falls back to searching
+        assert!(missing_socket.is_err());
+    }
+
+    #[test]
+    fn searches_sandbox_dirs_in_order() {
+        let base = Path::new("/run/user/1000");
+        let dirs = Socket::search_dirs_in(base);
+
+        assert_eq!(dirs[0], base);
+        assert_eq!(dirs[1], base.join("app/com.discordapp.Discord"));
+        assert!(dirs.contains(&base.join("app/com.discordapp.DiscordCanary")));
+        assert!(dirs.contains(&base.join("snap.discord")));
+        assert!(dirs.contains(&base.join("app/dev.vencord.Vesktop")));
+    }
+
+    #[test]
+    fn receives_split_and_coalesced_frames() {
+        let (stream, mut discord) = UnixStream::pair().unwrap();
+        stream.set_nonblocking(true).unwrap();
+        let mut socket = Socket {
+            stream,
+            slot: None,
+            read_buffer: BytesMut::new(),
+        };
+
+        let first = Message::new(OpCode::Frame, "ready").unwrap();
+        // Larger than a single read from the socket
+        let second = Message::new(OpCode::Frame, "x".repeat(5000)).unwrap();
+        let bytes = [first.encode().unwrap(), second.encode().unwrap()].concat();
+
+        discord.write_all(&bytes[..10]).unwrap();
+        assert!(socket.recv().unwrap_err().io_would_block());
+
+        discord.write_all(&bytes[10..]).unwrap();
+        assert_eq!(socket.recv().unwrap(), first);
+        assert_eq!(socket.recv().unwrap(), second);
+    }
+}
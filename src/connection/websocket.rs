@@ -0,0 +1,274 @@
+use super::base::Transport;
+use crate::{
+    error::{DiscordError, Result},
+    models::message::{Message, OpCode},
+};
+use std::{
+    io::{self, ErrorKind},
+    net::{Ipv4Addr, SocketAddr, TcpStream},
+    ops::RangeInclusive,
+    time,
+};
+use tungstenite::{
+    client::IntoClientRequest,
+    handshake::HandshakeError,
+    http::{header::ORIGIN, HeaderValue},
+    Message as WsMessage, WebSocket,
+};
+
+/// The ports Discord may serve RPC over WebSocket on, the same way it may listen on any of the IPC slots
+pub const WEBSOCKET_PORTS: RangeInclusive<u16> = 6463..=6472;
+
+/// A [`Transport`] over Discord's local WebSocket RPC server, rather than the IPC socket
+///
+/// Discord only accepts connections from the origins allowed in the RPC settings of the application.
+/// Only available with the `websocket` feature, and usually set with [`ClientBuilder::websocket`](crate::ClientBuilder::websocket).
+#[derive(Debug)]
+pub struct WebSocketTransport {
+    socket: WebSocket<TcpStream>,
+    slot: Option<u8>,
+}
+
+impl WebSocketTransport {
+    /// Connects to the first port Discord is listening on, from 6463 to 6472 on localhost
+    ///
+    /// # Errors
+    /// - Discord is not listening on any of the ports
+    /// - Discord rejected the connection, e.g. because `origin` is not allowed for the application
+    pub fn connect(client_id: u64, origin: &str) -> Result<Self> {
+        let candidates = WEBSOCKET_PORTS
+            .zip(0..)
+            .map(|(port, slot)| (SocketAddr::from((Ipv4Addr::LOCALHOST, port)), Some(slot)));
+
+        Self::connect_any(candidates, client_id, origin)
+    }
+
+    /// Connects to exactly the given address, rather than searching for Discord
+    ///
+    /// # Errors
+    /// - Nothing is listening on `addr`
+    /// - The server rejected the connection, e.g. because `origin` is not allowed for the application
+    pub fn connect_to(addr: SocketAddr, client_id: u64, origin: &str) -> Result<Self> {
+        Self::connect_any(std::iter::once((addr, None)), client_id, origin)
+    }
+
+    /// Tries each address in turn, and returns the first connection that succeeds.
+    fn connect_any<I>(candidates: I, client_id: u64, origin: &str) -> Result<Self>
+    where
+        I: IntoIterator<Item = (SocketAddr, Option<u8>)>,
+    {
+        let mut last_error = None;
+
+        for (addr, slot) in candidates {
+            match Self::open(addr, slot, client_id, origin) {
+                Ok(transport) => {
+                    trace!("Connected to ws://{}", addr);
+                    return Ok(transport);
+                }
+                Err(why) => {
+                    trace!("Failed to connect to ws://{}: {}", addr, why);
+                    last_error = Some(why);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or(DiscordError::ConnectionClosed))
+    }
+
+    fn open(addr: SocketAddr, slot: Option<u8>, client_id: u64, origin: &str) -> Result<Self> {
+        let mut request = format!("ws://{addr}/?v=1&client_id={client_id}&encoding=json")
+            .into_client_request()?;
+        request.headers_mut().insert(
+            ORIGIN,
+            HeaderValue::from_str(origin)
+                .map_err(|why| tungstenite::Error::HttpFormat(why.into()))?,
+        );
+
+        let stream = TcpStream::connect(addr)?;
+        stream.set_write_timeout(Some(time::Duration::from_secs(30)))?;
+        stream.set_read_timeout(Some(time::Duration::from_secs(30)))?;
+
+        let (socket, _) = tungstenite::client(request, stream).map_err(|why| match why {
+            HandshakeError::Failure(why) => DiscordError::from(why),
+            HandshakeError::Interrupted(_) => io::Error::from(ErrorKind::WouldBlock).into(),
+        })?;
+        // Only the handshake blocks, as the client sends its commands between reads
+        socket.get_ref().set_nonblocking(true)?;
+
+        Ok(Self { socket, slot })
+    }
+}
+
+impl Transport for WebSocketTransport {
+    fn send(&mut self, message: &Message) -> Result<()> {
+        let message = match message.opcode {
+            // The handshake is part of the connection URL
+            OpCode::Handshake => return Ok(()),
+            OpCode::Frame => WsMessage::Text(message.payload.clone()),
+            OpCode::Ping => WsMessage::Ping(message.payload.clone().into_bytes()),
+            OpCode::Pong => WsMessage::Pong(message.payload.clone().into_bytes()),
+            OpCode::Close => WsMessage::Close(None),
+        };
+
+        match self.socket.send(message) {
+            // Queued, and flushed on the next call
+            Err(tungstenite::Error::Io(why)) if why.kind() == ErrorKind::WouldBlock => {}
+            result => result?,
+        }
+
+        Ok(())
+    }
+
+    fn recv(&mut self) -> Result<Message> {
+        match self.socket.flush() {
+            Err(tungstenite::Error::Io(why)) if why.kind() == ErrorKind::WouldBlock => {}
+            result => result?,
+        }
+
+        loop {
+            let (opcode, payload) = match self.socket.read()? {
+                WsMessage::Text(payload) => (OpCode::Frame, payload),
+                WsMessage::Binary(payload) => (
+                    OpCode::Frame,
+                    String::from_utf8(payload).map_err(|_| DiscordError::Conversion)?,
+                ),
+                WsMessage::Pong(payload) => {
+                    (OpCode::Pong, String::from_utf8_lossy(&payload).into_owned())
+                }
+                WsMessage::Close(_) => return Err(DiscordError::ConnectionClosed),
+                // Pings are answered by tungstenite itself
+                WsMessage::Ping(_) | WsMessage::Frame(_) => continue,
+            };
+
+            let message = Message { opcode, payload };
+            trace!("<- {:?}", message);
+            return Ok(message);
+        }
+    }
+
+    fn slot(&self) -> Option<u8> {
+        self.slot
+    }
+}
+
+impl Drop for WebSocketTransport {
+    fn drop(&mut self) {
+        let closed = self.socket.close(None).is_ok() && self.socket.flush().is_ok();
+
+        if !closed {
+            trace!("Failed to properly close WebSocket");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, thread};
+    use tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        http::StatusCode,
+    };
+
+    const ALLOWED_ORIGIN: &str = "https://example.com";
+
+    /// Accepts a single client, as Discord would, replying to its first command
+    fn stub() -> (SocketAddr, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut uri = String::new();
+            // The error response type is set by tungstenite
+            #[allow(clippy::result_large_err)]
+            let check = |request: &Request, response: Response| {
+                uri = request.uri().to_string();
+                match request.headers().get(ORIGIN) {
+                    Some(origin) if origin == ALLOWED_ORIGIN => Ok(response),
+                    _ => {
+                        let mut response = ErrorResponse::new(Some("Invalid origin".to_owned()));
+                        *response.status_mut() = StatusCode::FORBIDDEN;
+                        Err(response)
+                    }
+                }
+            };
+            let Ok(mut socket) = tungstenite::accept_hdr(stream, check) else {
+                return uri;
+            };
+
+            socket
+                .send(WsMessage::Text(
+                    r#"{"cmd":"DISPATCH","evt":"READY","data":{"v":1},"nonce":null}"#.to_owned(),
+                ))
+                .unwrap();
+            if let WsMessage::Text(command) = socket.read().unwrap() {
+                let command: serde_json::Value = serde_json::from_str(&command).unwrap();
+                let response = serde_json::json!({ "cmd": command["cmd"], "data": {}, "nonce": command["nonce"] });
+                socket.send(WsMessage::Text(response.to_string())).unwrap();
+            }
+            // Wait for the client to close the connection
+            while socket.read().is_ok() {}
+
+            uri
+        });
+
+        (addr, server)
+    }
+
+    fn recv(transport: &mut WebSocketTransport) -> Message {
+        loop {
+            match transport.recv() {
+                Err(why) if why.io_would_block() => thread::sleep(time::Duration::from_millis(10)),
+                result => return result.unwrap(),
+            }
+        }
+    }
+
+    #[test]
+    fn connects_to_local_server() {
+        let closed = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let (addr, server) = stub();
+
+        assert!(matches!(
+            WebSocketTransport::connect_to(closed, 42, ALLOWED_ORIGIN),
+            Err(DiscordError::IoError(why)) if why.kind() == ErrorKind::ConnectionRefused
+        ));
+
+        // Skips the port nothing is listening on anymore
+        let mut transport = WebSocketTransport::connect_any(
+            [(closed, Some(0)), (addr, Some(1))],
+            42,
+            ALLOWED_ORIGIN,
+        )
+        .unwrap();
+        assert_eq!(transport.slot(), Some(1));
+
+        let ready = transport.handshake(42).unwrap();
+        assert!(ready.payload.contains("READY"));
+
+        let command = Message::new(
+            OpCode::Frame,
+            serde_json::json!({ "cmd": "SET_ACTIVITY", "args": {}, "nonce": "1" }),
+        )
+        .unwrap();
+        transport.send(&command).unwrap();
+        assert!(recv(&mut transport).payload.contains(r#""nonce":"1""#));
+
+        drop(transport);
+        assert_eq!(server.join().unwrap(), "/?v=1&client_id=42&encoding=json");
+    }
+
+    #[test]
+    fn rejected_origin_fails() {
+        let (addr, server) = stub();
+
+        let result = WebSocketTransport::connect_to(addr, 42, "https://evil.example.com");
+        server.join().unwrap();
+
+        assert!(matches!(result, Err(DiscordError::WebSocket(_))));
+    }
+}
@@ -1,22 +1,43 @@
-use super::base::Connection;
-use crate::Result;
+use super::base::{read_frame, Connection};
+use crate::{
+    error::{DiscordError, Result},
+    models::message::Message,
+};
+use bytes::BytesMut;
 use named_pipe::PipeClient;
-use std::{path::PathBuf, time};
+use std::{
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    time,
+};
+
+/// How long a read waits for Discord, short so that the client can send its commands in between
+const READ_TIMEOUT: time::Duration = time::Duration::from_millis(10);
 
 pub struct Socket {
-    socket: PipeClient,
+    stream: PipeClient,
+    slot: Option<u8>,
+    read_buffer: BytesMut,
 }
 
 impl Connection for Socket {
     type Socket = PipeClient;
 
-    fn connect() -> Result<Self> {
-        let connection_name = Self::socket_path(0);
-        let mut socket = PipeClient::connect(connection_name)?;
+    fn connect_to(path: &Path, slot: Option<u8>) -> Result<Self> {
+        let mut stream = PipeClient::connect(path)?;
         // Discord rate limit timeout is 15 seconds, so 16 should account for that
-        socket.set_write_timeout(Some(time::Duration::from_secs(16)));
-        socket.set_read_timeout(Some(time::Duration::from_secs(16)));
-        Ok(Self { socket })
+        stream.set_write_timeout(Some(time::Duration::from_secs(16)));
+        // Pipes cannot be non-blocking, so an idle read times out instead, see `recv`
+        stream.set_read_timeout(Some(READ_TIMEOUT));
+        Ok(Self {
+            stream,
+            slot,
+            read_buffer: BytesMut::new(),
+        })
+    }
+
+    fn slot(&self) -> Option<u8> {
+        self.slot
     }
 
     fn ipc_path() -> PathBuf {
@@ -24,6 +45,20 @@ impl Connection for Socket {
     }
 
     fn socket(&mut self) -> &mut Self::Socket {
-        &mut self.socket
+        &mut self.stream
+    }
+
+    fn read_buffer(&mut self) -> &mut BytesMut {
+        &mut self.read_buffer
+    }
+
+    fn recv(&mut self) -> Result<Message> {
+        match read_frame(&mut self.stream, &mut self.read_buffer) {
+            // Reported as `WouldBlock`, as the manager would otherwise take it for a lost connection
+            Err(DiscordError::IoError(why)) if why.kind() == ErrorKind::TimedOut => {
+                Err(io::Error::from(ErrorKind::WouldBlock).into())
+            }
+            result => result,
+        }
     }
 }
@@ -0,0 +1,304 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, OnceLock},
+    thread,
+};
+
+use crossbeam_channel::{unbounded, Sender};
+
+use crate::event_handler::{Context, Handler};
+
+/// How event handlers are called when an event arrives
+///
+/// # Examples
+///
+/// ```no_run
+/// # use discord_presence::{Client, DispatchMode};
+/// let mut drpc = Client::builder(1003450375732482138)
+///     .dispatch_mode(DispatchMode::Pool(4))
+///     .build();
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum DispatchMode {
+    /// Call the handlers on the thread receiving from Discord, one after the other
+    ///
+    /// Handlers run in the order events arrive, and in the order they were registered,
+    /// but nothing is received from Discord while a handler runs, so they must be quick,
+    /// and must not wait for the response to a command.
+    Inline,
+    /// Call the handlers on a single dispatcher thread, one after the other
+    ///
+    /// Handlers run in the order events arrive, and in the order they were registered,
+    /// without holding up the connection.
+    #[default]
+    Dedicated,
+    /// Call the handlers on a fixed number of worker threads
+    ///
+    /// Handlers may run concurrently, and in any order,
+    /// but never on more threads than given.
+    Pool(usize),
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Runs event handlers according to a [`DispatchMode`]
+pub(crate) struct Dispatcher {
+    mode: DispatchMode,
+    /// Sends jobs to the worker threads once they are started, unless dispatching inline
+    jobs: OnceLock<Option<Sender<Job>>>,
+}
+
+impl Dispatcher {
+    pub(crate) fn new(mode: DispatchMode) -> Self {
+        Self {
+            mode,
+            jobs: OnceLock::new(),
+        }
+    }
+
+    /// Spawns the worker threads, if they are not running yet
+    ///
+    /// Called when the client starts, rather than when it is built, so that a client that is never started spawns no threads.
+    /// Dispatching before then starts them too.
+    pub(crate) fn start(&self) {
+        self.jobs();
+    }
+
+    fn jobs(&self) -> Option<&Sender<Job>> {
+        self.jobs.get_or_init(|| spawn_workers(self.mode)).as_ref()
+    }
+
+    /// Calls the handlers with the context of an event
+    pub(crate) fn dispatch(&self, handlers: Vec<Arc<Handler>>, context: Context) {
+        let Some(jobs) = self.jobs() else {
+            call_all(&handlers, &context);
+            return;
+        };
+
+        let sent = if let DispatchMode::Pool(_) = self.mode {
+            // Each handler is a separate job, so that they are spread over the pool
+            handlers.into_iter().all(|handler| {
+                let context = context.clone();
+                jobs.send(Box::new(move || call(&handler, context))).is_ok()
+            })
+        } else {
+            // A single job, so that the handlers of one event finish before those of the next
+            jobs.send(Box::new(move || call_all(&handlers, &context)))
+                .is_ok()
+        };
+
+        if !sent {
+            error!("Event dispatcher stopped, dropping event");
+        }
+    }
+}
+
+/// Spawns the worker threads for `mode`, returning the sender for their jobs, or `None` if dispatching inline
+fn spawn_workers(mode: DispatchMode) -> Option<Sender<Job>> {
+    let workers = match mode {
+        DispatchMode::Inline => 0,
+        DispatchMode::Dedicated => 1,
+        DispatchMode::Pool(size) => size.max(1),
+    };
+
+    (workers > 0).then(|| {
+        let (tx, rx) = unbounded::<Job>();
+
+        for n in 0..workers {
+            let rx = rx.clone();
+            thread::Builder::new()
+                .name(format!("discord-presence-dispatch-{n}"))
+                .spawn(move || {
+                    // Stops once the registry, and with it the sender, is dropped
+                    for job in rx {
+                        job();
+                    }
+                })
+                .expect("Failed to spawn event dispatcher thread");
+        }
+
+        tx
+    })
+}
+
+fn call_all(handlers: &[Arc<Handler>], context: &Context) {
+    for handler in handlers {
+        call(handler, context.clone());
+    }
+}
+
+/// Calls a handler, making sure a panicking handler does not take the dispatcher down with it
+fn call(handler: &Arc<Handler>, context: Context) {
+    if panic::catch_unwind(AssertUnwindSafe(|| handler(context))).is_err() {
+        error!("Event handler panicked");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Event, EventData};
+    use crossbeam_channel::Receiver;
+    use serde_json::Value as JsonValue;
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    /// A handler call, as `(handler, event, thread name)`
+    type Call = (usize, u64, String);
+
+    fn context(n: u64) -> Context {
+        Context::new(Event::Ready, EventData::Unknown(JsonValue::from(n)))
+    }
+
+    fn number(context: &Context) -> u64 {
+        match &context.event {
+            EventData::Unknown(value) => value.as_u64().unwrap(),
+            other => panic!("unexpected event data: {:?}", other),
+        }
+    }
+
+    /// Handlers that each record their [`Call`]s
+    fn recording_handlers(count: usize) -> (Vec<Arc<Handler>>, Receiver<Call>) {
+        let (tx, rx) = unbounded();
+        let handlers = (0..count)
+            .map(|handler| {
+                let tx = tx.clone();
+                Arc::new(move |context: Context| {
+                    let thread = thread::current().name().unwrap_or_default().to_owned();
+                    tx.send((handler, number(&context), thread)).unwrap();
+                }) as Arc<Handler>
+            })
+            .collect();
+
+        (handlers, rx)
+    }
+
+    fn dispatch_all(dispatcher: &Dispatcher, handlers: &[Arc<Handler>], events: u64) {
+        for n in 0..events {
+            dispatcher.dispatch(handlers.to_vec(), context(n));
+        }
+    }
+
+    fn receive(rx: &Receiver<Call>, count: usize) -> Vec<Call> {
+        (0..count)
+            .map(|_| rx.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect()
+    }
+
+    fn in_order(handlers: usize, events: u64) -> Vec<(usize, u64)> {
+        (0..events)
+            .flat_map(|event| (0..handlers).map(move |handler| (handler, event)))
+            .collect()
+    }
+
+    #[test]
+    fn inline_calls_in_order_on_current_thread() {
+        let dispatcher = Dispatcher::new(DispatchMode::Inline);
+        let (handlers, rx) = recording_handlers(3);
+
+        dispatch_all(&dispatcher, &handlers, 50);
+        // Everything has already run by the time `dispatch` returns
+        let calls: Vec<_> = rx.try_iter().collect();
+
+        let current = thread::current().name().unwrap_or_default().to_owned();
+        assert!(calls.iter().all(|(_, _, thread)| *thread == current));
+        assert_eq!(
+            calls
+                .into_iter()
+                .map(|(h, e, _)| (h, e))
+                .collect::<Vec<_>>(),
+            in_order(3, 50)
+        );
+    }
+
+    #[test]
+    fn dedicated_calls_in_order_on_one_thread() {
+        let dispatcher = Dispatcher::new(DispatchMode::Dedicated);
+        let (handlers, rx) = recording_handlers(3);
+
+        dispatch_all(&dispatcher, &handlers, 50);
+        let calls = receive(&rx, 150);
+
+        assert!(calls
+            .iter()
+            .all(|(_, _, thread)| thread == "discord-presence-dispatch-0"));
+        assert_eq!(
+            calls
+                .into_iter()
+                .map(|(h, e, _)| (h, e))
+                .collect::<Vec<_>>(),
+            in_order(3, 50)
+        );
+    }
+
+    #[test]
+    fn pool_calls_every_handler_on_bounded_threads() {
+        let dispatcher = Dispatcher::new(DispatchMode::Pool(2));
+        let (handlers, rx) = recording_handlers(3);
+
+        dispatch_all(&dispatcher, &handlers, 50);
+        let calls = receive(&rx, 150);
+
+        // Any order, but each handler is called exactly once per event, on one of the 2 workers
+        let mut received: Vec<_> = calls.iter().map(|(h, e, _)| (*h, *e)).collect();
+        received.sort_by_key(|(handler, event)| (*event, *handler));
+        assert_eq!(received, in_order(3, 50));
+        assert!(calls
+            .iter()
+            .all(|(_, _, thread)| thread == "discord-presence-dispatch-0"
+                || thread == "discord-presence-dispatch-1"));
+    }
+
+    #[test]
+    fn pool_runs_at_most_size_handlers_at_once() {
+        let dispatcher = Dispatcher::new(DispatchMode::Pool(3));
+        let running = Arc::new(AtomicUsize::new(0));
+        let most = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = unbounded();
+
+        let handler: Arc<Handler> = {
+            let running = running.clone();
+            let most = most.clone();
+            Arc::new(move |_| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(5));
+                running.fetch_sub(1, Ordering::SeqCst);
+                tx.send(()).unwrap();
+            })
+        };
+
+        dispatch_all(&dispatcher, &[handler], 30);
+        for _ in 0..30 {
+            rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+
+        assert!(most.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[test]
+    fn spawns_workers_once_started() {
+        let dispatcher = Dispatcher::new(DispatchMode::Pool(2));
+        assert!(dispatcher.jobs.get().is_none());
+
+        dispatcher.start();
+        assert!(dispatcher.jobs.get().unwrap().is_some());
+    }
+
+    #[test]
+    fn survives_panicking_handlers() {
+        let dispatcher = Dispatcher::new(DispatchMode::Dedicated);
+        let (tx, rx) = unbounded();
+        let handlers: Vec<Arc<Handler>> = vec![
+            Arc::new(|_| panic!("handler panicked")),
+            Arc::new(move |context| tx.send(number(&context)).unwrap()),
+        ];
+
+        dispatch_all(&dispatcher, &handlers, 2);
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(0));
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(1));
+    }
+}
@@ -48,6 +48,14 @@ pub enum DiscordError {
     #[error("Error subscribing to an event")]
     /// Subscription Joining Error
     SubscriptionFailed,
+    #[error("Discord rejected the command: {message} ({code})")]
+    /// Discord replied to a command with an error
+    CommandFailed {
+        /// The error code Discord sent, see the [RPC error codes](https://discord.com/developers/docs/topics/opcodes-and-status-codes#rpc-rpc-error-codes)
+        code: u32,
+        /// The message Discord sent with the error
+        message: String,
+    },
     #[error("Connection was closed prematurely")]
     /// Connection Closing error
     ConnectionClosed,
@@ -60,6 +68,30 @@ pub enum DiscordError {
     /// No changes were made to the event handler
     #[error("No changes were made to the event handler. This can usually be ignored")]
     NoChangesMade,
+    #[error("The client stopped before the event was received")]
+    /// The client stopped while waiting for an event
+    ClientStopped,
+    #[error("Could not exchange the authorization code for an access token: {0}")]
+    /// The code-for-token exchange given to [`Client::authorize_and_authenticate`](crate::Client::authorize_and_authenticate) failed
+    TokenExchange(Box<dyn std::error::Error + Send + Sync>),
+    #[cfg(feature = "websocket")]
+    #[error("WebSocket error: {0}")]
+    /// The WebSocket transport ran into an error, other than an IO error or the connection closing
+    WebSocket(Box<tungstenite::Error>),
+    #[error(
+        "Received a frame of {0} bytes, larger than the maximum of {}",
+        crate::models::message::MAX_PAYLOAD_LEN
+    )]
+    /// A frame declared a payload longer than [`MAX_PAYLOAD_LEN`](crate::models::message::MAX_PAYLOAD_LEN)
+    FrameTooLarge(usize),
+    #[error("Sent {actual}, but the transcript recorded {expected}")]
+    /// A message sent while replaying a transcript is not the one recorded next
+    TranscriptMismatch {
+        /// The message recorded next
+        expected: String,
+        /// The message actually sent
+        actual: String,
+    },
     #[error("Could not safely shut down client. Thread is in use.")]
     /// RPC thread is in use
     ThreadInUse,
@@ -76,6 +108,10 @@ impl DiscordError {
     }
 
     #[must_use]
+    #[deprecated(
+        since = "1.2.0",
+        note = "the client no longer stops on refused connections, its `ReconnectPolicy` decides whether to retry"
+    )]
     /// Checks if the error should break the connection
     pub fn should_break(&self) -> bool {
         match self {
@@ -85,5 +121,18 @@ impl DiscordError {
     }
 }
 
+#[cfg(feature = "websocket")]
+impl From<tungstenite::Error> for DiscordError {
+    fn from(why: tungstenite::Error) -> Self {
+        match why {
+            tungstenite::Error::Io(why) => Self::IoError(why),
+            tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+                Self::ConnectionClosed
+            }
+            why => Self::WebSocket(Box::new(why)),
+        }
+    }
+}
+
 /// Result type for Discord RPC error types
 pub type Result<T> = StdResult<T, DiscordError>;
@@ -1,11 +1,17 @@
+use std::sync::Weak;
 use std::{collections::HashMap, sync::Arc};
-use std::{sync::Weak, thread};
 
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
+use serde_json::Value as JsonValue;
 
-use crate::models::{Event, EventData};
+use crate::{
+    connection::ConnectionState,
+    dispatch::{DispatchMode, Dispatcher},
+    event_stream::{Events, OverflowPolicy, Queue},
+    models::{ConnectionStateEvent, Event, EventData},
+};
 
-type Handler = dyn Fn(Context) + 'static + Send + Sync;
+pub(crate) type Handler = dyn Fn(Context) + 'static + Send + Sync;
 
 type HandlerList = Vec<Arc<Handler>>;
 
@@ -13,19 +19,61 @@ type HandlerList = Vec<Arc<Handler>>;
 pub struct Context {
     // TODO: implement event data structures
     pub event: EventData,
+    /// The event the data is for, needed by raw handlers
+    pub(crate) kind: Kind,
 }
 
 impl Context {
-    pub fn new(event: EventData) -> Self {
-        Self { event }
+    pub fn new(kind: Event, event: EventData) -> Self {
+        Self {
+            event,
+            kind: Kind::Event(kind),
+        }
     }
 }
 
-type Handlers = RwLock<HashMap<Event, HandlerList>>;
+/// The event a [`Context`] is for
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) enum Kind {
+    Event(Event),
+    /// An event the crate does not know, by the name Discord sent it with
+    Unknown(String),
+}
+
+impl Kind {
+    /// The name Discord sent the event with, e.g. `ACTIVITY_JOIN`
+    pub(crate) fn name(&self) -> String {
+        match self {
+            Self::Event(event) => serde_json::to_value(event)
+                .ok()
+                .and_then(|name| name.as_str().map(str::to_owned))
+                // Only the client-side events have no name, and those are never raw
+                .unwrap_or_else(|| format!("{event:?}")),
+            Self::Unknown(name) => name.clone(),
+        }
+    }
+}
+
+/// What a handler is registered for
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub(crate) enum HandlerKey {
+    /// Every occurrence of one event
+    Event(Event),
+    /// Any event whose data could not be parsed
+    Raw,
+}
+
+impl From<Event> for HandlerKey {
+    fn from(event: Event) -> Self {
+        Self::Event(event)
+    }
+}
+
+type Handlers = RwLock<HashMap<HandlerKey, HandlerList>>;
 
 #[must_use = "event listeners will be immediately dropped if the handle is not kept. Use `.persist` to stop them from being removed."]
 pub struct EventCallbackHandle {
-    event: Event,
+    key: HandlerKey,
     registry: Weak<HandlerRegistry>,
     handler: Weak<Handler>,
 }
@@ -46,7 +94,7 @@ impl Drop for EventCallbackHandle {
     fn drop(&mut self) {
         // if the registry or this event handler has already been dropped, there's no reason to try and do it again
         if let (Some(registry), Some(handler)) = (self.registry.upgrade(), self.handler.upgrade()) {
-            let handler = registry.remove(self.event, &handler);
+            let handler = registry.remove(self.key, &handler);
             if handler.is_err() {
                 error!("Failed to remove event handler. This can usually be ignored.");
             }
@@ -54,63 +102,210 @@ impl Drop for EventCallbackHandle {
     }
 }
 
+/// The queues of all [`Events`] streams
+#[derive(Default)]
+struct Streams {
+    queues: Vec<Weak<Queue>>,
+    /// Set once the client stops, so that streams created afterwards end right away
+    closed: bool,
+}
+
 pub struct HandlerRegistry {
     handlers: Handlers,
+    streams: Mutex<Streams>,
+    dispatcher: Dispatcher,
 }
 
 impl HandlerRegistry {
-    pub fn new() -> Self {
+    pub fn with_dispatch_mode(mode: DispatchMode) -> Self {
         Self {
             handlers: RwLock::new(HashMap::new()),
+            streams: Mutex::new(Streams::default()),
+            dispatcher: Dispatcher::new(mode),
+        }
+    }
+
+    /// Creates a stream that receives every event handled from now on
+    pub fn stream(&self, capacity: usize, policy: OverflowPolicy) -> Events {
+        let events = Events::new(capacity, policy);
+        let mut streams = self.streams.lock();
+
+        if streams.closed {
+            events.queue().close();
+        } else {
+            streams.queues.push(Arc::downgrade(events.queue()));
+        }
+
+        events
+    }
+
+    /// Spawns the threads handlers are called on, if the dispatch mode needs any
+    pub fn start_dispatcher(&self) {
+        self.dispatcher.start();
+    }
+
+    /// Ends all streams, e.g. because the client has stopped
+    pub fn close_streams(&self) {
+        let mut streams = self.streams.lock();
+        streams.closed = true;
+
+        for queue in streams.queues.drain(..) {
+            if let Some(queue) = queue.upgrade() {
+                queue.close();
+            }
         }
     }
 
+    /// Lets streams receive events again, e.g. because the client was started again
+    pub fn reopen_streams(&self) {
+        self.streams.lock().closed = false;
+    }
+
+    /// Pushes an event to every stream, forgetting those that were dropped
+    fn push_to_streams(&self, event: Event, data: &EventData) {
+        self.streams
+            .lock()
+            .queues
+            .retain(|queue| match queue.upgrade() {
+                Some(queue) => {
+                    queue.push(event, data.clone());
+                    true
+                }
+                None => false,
+            });
+    }
+
     pub fn register<F>(self: &Arc<Self>, event: Event, handler: F) -> EventCallbackHandle
+    where
+        F: Fn(Context) + Send + Sync + 'static,
+    {
+        self.insert(event.into(), Arc::new(handler))
+    }
+
+    /// Registers a handler, and calls it right away with `data`, e.g. to replay an event it missed
+    pub fn register_and_replay<F>(
+        self: &Arc<Self>,
+        event: Event,
+        handler: F,
+        data: EventData,
+    ) -> EventCallbackHandle
     where
         F: Fn(Context) + Send + Sync + 'static,
     {
         let handler: Arc<Handler> = Arc::new(handler);
+        let callback_handle = self.insert(event.into(), handler.clone());
+        self.dispatcher
+            .dispatch(vec![handler], Context::new(event, data));
+
+        callback_handle
+    }
+
+    /// Registers a handler for any event whose data could not be parsed, i.e. is [`EventData::Unknown`],
+    /// and for any event the crate does not know
+    pub fn register_raw<F>(self: &Arc<Self>, handler: F) -> EventCallbackHandle
+    where
+        F: Fn(Context) + Send + Sync + 'static,
+    {
+        self.insert(HandlerKey::Raw, Arc::new(handler))
+    }
+
+    fn insert(self: &Arc<Self>, key: HandlerKey, handler: Arc<Handler>) -> EventCallbackHandle {
         let callback_handle = EventCallbackHandle {
-            event,
+            key,
             registry: Arc::downgrade(self),
             handler: Arc::downgrade(&handler),
         };
 
         let mut event_handlers = self.handlers.write();
-        let event_handler = event_handlers.entry(event).or_default();
+        let event_handler = event_handlers.entry(key).or_default();
         event_handler.push(handler);
 
         callback_handle
     }
 
     // TODO: Replace data type with stronger types
+    /// Calls the handlers for an event, according to the registry's [`DispatchMode`]
     pub fn handle(&self, event: Event, data: EventData) {
-        let handlers = self.handlers.read();
-        if let Some(handlers) = handlers.get(&event) {
-            let context = Context::new(data);
-
-            for handler in handlers {
-                let handler = handler.clone();
-                let context = context.clone();
-                thread::spawn(move || {
-                    handler(context);
-                });
-            }
+        self.push_to_streams(event, &data);
+
+        // Cloned, so that handlers can register or remove handlers themselves when dispatched inline
+        let handlers: HandlerList = {
+            let registered = self.handlers.read();
+            let raw = match data {
+                EventData::Unknown(_) => registered.get(&HandlerKey::Raw),
+                _ => None,
+            };
+
+            registered
+                .get(&event.into())
+                .into_iter()
+                .chain(raw)
+                .flatten()
+                .cloned()
+                .collect()
+        };
+
+        if !handlers.is_empty() {
+            self.dispatcher
+                .dispatch(handlers, Context::new(event, data));
+        }
+    }
+
+    /// Calls the raw handlers for an event the crate does not know, see [`HandlerRegistry::register_raw`]
+    pub fn handle_unknown(&self, name: String, data: JsonValue) {
+        let handlers: HandlerList = self
+            .handlers
+            .read()
+            .get(&HandlerKey::Raw)
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+
+        if !handlers.is_empty() {
+            let context = Context {
+                event: EventData::Unknown(data),
+                kind: Kind::Unknown(name),
+            };
+            self.dispatcher.dispatch(handlers, context);
         }
     }
 
+    /// Fires the events for the connection moving from `previous` to `current`
+    ///
+    /// `lost` is whether an actual connection was lost, rather than a connection attempt failing,
+    /// as [`Event::Disconnected`] is only fired for the former.
+    pub fn connection_state_changed(
+        &self,
+        previous: ConnectionState,
+        current: ConnectionState,
+        lost: bool,
+    ) {
+        trace!("Connection state changed: {:?} -> {:?}", previous, current);
+
+        let data = EventData::ConnectionState(ConnectionStateEvent { previous, current });
+
+        match current {
+            ConnectionState::Connected => self.handle(Event::Connected, data.clone()),
+            ConnectionState::Disconnected if lost => self.handle(Event::Disconnected, data.clone()),
+            _ => {}
+        }
+
+        self.handle(Event::ConnectionStateChanged, data);
+    }
+
     /// Removes a handler from the registry, if it exists
     ///
     /// # Errors
     /// - Returns an error if no changes were made to the registry. This generally means that the handler has already been removed, and can thus generally be ignored.
     // TODO: Change return type to Result
-    pub fn remove(
+    pub(crate) fn remove(
         self: &Arc<Self>,
-        event: Event,
+        key: HandlerKey,
         target: &Arc<Handler>,
     ) -> crate::Result<Arc<Handler>> {
         let mut handlers = self.handlers.write();
-        if let Some(handlers) = handlers.get_mut(&event) {
+        if let Some(handlers) = handlers.get_mut(&key) {
             if let Some(index) = handlers.iter().position(|handler| {
                 // Allowed address comparison as we need to compare the function pointers, rather than the actual functions
                 #[allow(clippy::vtable_address_comparisons)]
@@ -124,6 +319,18 @@ impl HandlerRegistry {
     }
 }
 
+impl Default for HandlerRegistry {
+    fn default() -> Self {
+        Self::with_dispatch_mode(DispatchMode::default())
+    }
+}
+
+impl Drop for HandlerRegistry {
+    fn drop(&mut self) {
+        self.close_streams();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::mem::forget;
@@ -132,21 +339,21 @@ mod tests {
 
     #[test]
     fn can_register_event_handlers() {
-        let registry = Arc::new(HandlerRegistry::new());
+        let registry = Arc::new(HandlerRegistry::default());
         let _ready1 = registry.register(Event::Ready, |_| unimplemented!());
         let _ready2 = registry.register(Event::Ready, |_| unimplemented!());
         let _error = registry.register(Event::Error, |_| unimplemented!());
 
         let handlers = registry.handlers.read();
         assert_eq!(handlers.len(), 2);
-        assert_eq!(handlers[&Event::Ready].len(), 2);
-        assert_eq!(handlers[&Event::Error].len(), 1);
+        assert_eq!(handlers[&Event::Ready.into()].len(), 2);
+        assert_eq!(handlers[&Event::Error.into()].len(), 1);
     }
 
     /// Removes event handlers once they go out of scope to prevent memory leaks
     #[test]
     fn auto_remove_event_handlers() {
-        let registry = Arc::new(HandlerRegistry::new());
+        let registry = Arc::new(HandlerRegistry::default());
         let _ready1 = registry.register(Event::Ready, |_| unimplemented!());
         let _error = registry.register(Event::Error, |_| unimplemented!());
 
@@ -157,15 +364,15 @@ mod tests {
 
         let handlers = registry.handlers.read();
         assert_eq!(handlers.len(), 2);
-        assert_eq!(handlers[&Event::Ready].len(), 1);
-        assert_eq!(handlers[&Event::Error].len(), 1);
+        assert_eq!(handlers[&Event::Ready.into()].len(), 1);
+        assert_eq!(handlers[&Event::Error.into()].len(), 1);
     }
 
     /// Enables keeping an event callback for the entire lifetime of the client.
     /// This disables the functionality tested in `auto_remove_event_handlers`.
     #[test]
     fn forget_cb_handles() {
-        let registry = Arc::new(HandlerRegistry::new());
+        let registry = Arc::new(HandlerRegistry::default());
 
         {
             let ready = registry.register(Event::Ready, |_| unimplemented!());
@@ -176,6 +383,55 @@ mod tests {
 
         let handlers = registry.handlers.read();
         assert_eq!(handlers.len(), 1);
-        assert_eq!(handlers[&Event::Ready].len(), 1);
+        assert_eq!(handlers[&Event::Ready.into()].len(), 1);
+    }
+
+    #[test]
+    fn streams_receive_every_event_until_closed() {
+        let registry = HandlerRegistry::default();
+        let events = registry.stream(16, OverflowPolicy::default());
+        let dropped = registry.stream(16, OverflowPolicy::default());
+        drop(dropped);
+
+        registry.handle(Event::Ready, EventData::Unknown(JsonValue::Null));
+        registry.handle(Event::Error, EventData::Unknown(JsonValue::Null));
+        registry.close_streams();
+
+        let received: Vec<_> = events.map(|(event, _)| event).collect();
+        assert_eq!(received, vec![Event::Ready, Event::Error]);
+        assert!(registry.streams.lock().queues.is_empty());
+        // Streams created after closing end right away
+        assert_eq!(registry.stream(16, OverflowPolicy::default()).recv(), None);
+    }
+
+    #[test]
+    fn raw_handlers_receive_unparsed_data() {
+        let registry = Arc::new(HandlerRegistry::with_dispatch_mode(DispatchMode::Inline));
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let _raw = registry.register_raw(move |ctx| tx.send((ctx.kind, ctx.event)).unwrap());
+
+        registry.handle(
+            Event::Error,
+            EventData::Error(crate::models::ErrorEvent {
+                code: Some(4000),
+                message: None,
+            }),
+        );
+        registry.handle(Event::Ready, EventData::Unknown(JsonValue::Null));
+        registry.handle_unknown("NEW_EVENT".to_owned(), JsonValue::Null);
+
+        assert_eq!(
+            rx.try_iter().collect::<Vec<_>>(),
+            vec![
+                (
+                    Kind::Event(Event::Ready),
+                    EventData::Unknown(JsonValue::Null)
+                ),
+                (
+                    Kind::Unknown("NEW_EVENT".to_owned()),
+                    EventData::Unknown(JsonValue::Null)
+                ),
+            ]
+        );
     }
 }
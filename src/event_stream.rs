@@ -0,0 +1,285 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use parking_lot::{Condvar, Mutex};
+
+use crate::models::{Event, EventData};
+
+/// The number of events buffered by [`Client::events`](crate::Client::events)
+pub const DEFAULT_EVENT_CAPACITY: usize = 256;
+
+/// What happens when an event arrives while the buffer of an [`Events`] stream is full
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum OverflowPolicy {
+    /// Drop the oldest buffered event to make room, so the stream always has the latest events
+    #[default]
+    DropOldest,
+    /// Drop the event that just arrived, so the stream has the events that were missed first
+    DropNewest,
+}
+
+#[derive(Debug, Default)]
+struct Buffer {
+    events: VecDeque<(Event, EventData)>,
+    dropped: u64,
+    closed: bool,
+    #[cfg(feature = "async")]
+    waker: Option<std::task::Waker>,
+}
+
+/// The receiving end of an [`Events`] stream, kept by the event handler registry
+#[derive(Debug)]
+pub(crate) struct Queue {
+    buffer: Mutex<Buffer>,
+    available: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+impl Queue {
+    pub(crate) fn push(&self, event: Event, data: EventData) {
+        let mut buffer = self.buffer.lock();
+
+        if buffer.events.len() >= self.capacity {
+            buffer.dropped += 1;
+
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    buffer.events.pop_front();
+                }
+                OverflowPolicy::DropNewest => return,
+            }
+        }
+
+        buffer.events.push_back((event, data));
+        self.notify(&mut buffer);
+    }
+
+    /// Ends the stream, once the buffered events have been received
+    pub(crate) fn close(&self) {
+        let mut buffer = self.buffer.lock();
+        buffer.closed = true;
+        self.notify(&mut buffer);
+    }
+
+    fn notify(&self, buffer: &mut Buffer) {
+        self.available.notify_all();
+
+        #[cfg(feature = "async")]
+        if let Some(waker) = buffer.waker.take() {
+            waker.wake();
+        }
+        #[cfg(not(feature = "async"))]
+        let _ = buffer;
+    }
+}
+
+/// A stream of every event received by a client, as `(Event, EventData)` in arrival order
+///
+/// Created with [`Client::events`](crate::Client::events).
+/// Events are buffered until they are received, up to a fixed capacity,
+/// after which the [`OverflowPolicy`] decides which events are dropped.
+///
+/// The stream ends once the client stops, after the remaining buffered events have been received.
+/// With the `async` feature, this also implements `futures_core::Stream`.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use discord_presence::{Client, Event};
+/// let mut drpc = Client::new(1003450375732482138);
+/// let events = drpc.events();
+///
+/// drpc.start();
+///
+/// for (event, data) in events {
+///     println!("{:?}: {:?}", event, data);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Events {
+    queue: Arc<Queue>,
+}
+
+impl Events {
+    pub(crate) fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            queue: Arc::new(Queue {
+                buffer: Mutex::new(Buffer::default()),
+                available: Condvar::new(),
+                capacity: capacity.max(1),
+                policy,
+            }),
+        }
+    }
+
+    pub(crate) fn queue(&self) -> &Arc<Queue> {
+        &self.queue
+    }
+
+    #[must_use]
+    /// Blocks until the next event arrives, or returns `None` once the client has stopped
+    pub fn recv(&self) -> Option<(Event, EventData)> {
+        let mut buffer = self.queue.buffer.lock();
+
+        loop {
+            if let Some(event) = buffer.events.pop_front() {
+                return Some(event);
+            }
+
+            if buffer.closed {
+                return None;
+            }
+
+            self.queue.available.wait(&mut buffer);
+        }
+    }
+
+    #[must_use]
+    /// Like [`Events::recv`], but gives up after waiting for `timeout`
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<(Event, EventData)> {
+        let mut buffer = self.queue.buffer.lock();
+
+        if buffer.events.is_empty() && !buffer.closed {
+            self.queue.available.wait_for(&mut buffer, timeout);
+        }
+
+        buffer.events.pop_front()
+    }
+
+    #[must_use]
+    /// Returns the next buffered event, without waiting
+    pub fn try_recv(&self) -> Option<(Event, EventData)> {
+        self.queue.buffer.lock().events.pop_front()
+    }
+
+    #[must_use]
+    /// Whether the stream has ended, i.e. the client has stopped and every buffered event was received
+    pub fn is_finished(&self) -> bool {
+        let buffer = self.queue.buffer.lock();
+        buffer.closed && buffer.events.is_empty()
+    }
+
+    #[must_use]
+    /// The number of events dropped so far because the buffer was full
+    pub fn dropped(&self) -> u64 {
+        self.queue.buffer.lock().dropped
+    }
+}
+
+impl Iterator for Events {
+    type Item = (Event, EventData);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for Events {
+    type Item = (Event, EventData);
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        let mut buffer = self.queue.buffer.lock();
+
+        if let Some(event) = buffer.events.pop_front() {
+            return std::task::Poll::Ready(Some(event));
+        }
+
+        if buffer.closed {
+            return std::task::Poll::Ready(None);
+        }
+
+        buffer.waker = Some(cx.waker().clone());
+        std::task::Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ErrorEvent;
+    use std::thread;
+
+    fn error(code: u32) -> EventData {
+        EventData::Error(ErrorEvent {
+            code: Some(code),
+            message: None,
+        })
+    }
+
+    fn codes(events: &Events) -> Vec<u32> {
+        std::iter::from_fn(|| events.try_recv())
+            .map(|(_, data)| match data {
+                EventData::Error(ErrorEvent {
+                    code: Some(code), ..
+                }) => code,
+                other => panic!("unexpected event data: {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn drops_oldest_when_full() {
+        let events = Events::new(3, OverflowPolicy::DropOldest);
+        for code in 0..5 {
+            events.queue().push(Event::Error, error(code));
+        }
+
+        assert_eq!(codes(&events), vec![2, 3, 4]);
+        assert_eq!(events.dropped(), 2);
+    }
+
+    #[test]
+    fn drops_newest_when_full() {
+        let events = Events::new(3, OverflowPolicy::DropNewest);
+        for code in 0..5 {
+            events.queue().push(Event::Error, error(code));
+        }
+
+        assert_eq!(codes(&events), vec![0, 1, 2]);
+        assert_eq!(events.dropped(), 2);
+    }
+
+    #[test]
+    fn ends_after_buffered_events_once_closed() {
+        let events = Events::new(16, OverflowPolicy::default());
+        let queue = events.queue().clone();
+
+        let producer = thread::spawn(move || {
+            for code in 0..10 {
+                queue.push(Event::Error, error(code));
+            }
+            queue.close();
+        });
+
+        let received: Vec<_> = events.map(|(event, _)| event).collect();
+        producer.join().unwrap();
+
+        assert_eq!(received, vec![Event::Error; 10]);
+    }
+
+    #[cfg(feature = "async")]
+    #[tokio::test]
+    async fn implements_stream() {
+        use futures_core::Stream;
+        use std::{future::poll_fn, pin::Pin};
+
+        let mut events = Events::new(16, OverflowPolicy::default());
+        let queue = events.queue().clone();
+
+        let producer = tokio::spawn(async move {
+            queue.push(Event::Ready, error(0));
+            queue.close();
+        });
+
+        let first = poll_fn(|cx| Pin::new(&mut events).poll_next(cx)).await;
+        let end = poll_fn(|cx| Pin::new(&mut events).poll_next(cx)).await;
+        producer.await.unwrap();
+
+        assert_eq!(first.map(|(event, _)| event), Some(Event::Ready));
+        assert_eq!(end, None);
+    }
+}
@@ -9,8 +9,6 @@
 
 //! A Rust library that allows the developer to interact with the Discord Presence API with ease
 
-pub(crate) static READY: AtomicBool = AtomicBool::new(false);
-
 // Cannot remove this *macro_use*, would break derive inside of macros
 #[macro_use]
 extern crate serde;
@@ -20,18 +18,34 @@ extern crate tracing;
 
 #[macro_use]
 mod macros;
+/// An async client for the Discord Presence API, running on tokio
+#[cfg(feature = "async")]
+pub mod async_client;
 /// A client for the Discord Presence API
 pub mod client;
 mod connection;
+mod dispatch;
 /// Errors that can occur when interacting with the Discord Presence API
 pub mod error;
 mod event_handler;
+mod event_stream;
 /// Models for discord activity
 pub mod models;
+mod request;
+/// A mock Discord IPC server, to test code using the client without Discord
+#[cfg(all(feature = "test-util", unix))]
+pub mod test_util;
 mod utils;
 
-use std::sync::atomic::AtomicBool;
-
-pub use client::Client;
+#[cfg(feature = "async")]
+pub use async_client::AsyncClient;
+pub use client::{Client, ClientBuilder};
+pub use connection::{
+    ConnectionState, MessageDirection, ReconnectPolicy, StreamTransport, TranscriptEntry, Transport,
+};
+#[cfg(feature = "websocket")]
+pub use connection::{WebSocketTransport, WEBSOCKET_PORTS};
+pub use dispatch::DispatchMode;
 pub use error::{DiscordError, Result};
+pub use event_stream::{Events, OverflowPolicy};
 pub use models::Event;
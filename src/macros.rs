@@ -75,9 +75,32 @@ macro_rules! into_error {
     };
 }
 
+macro_rules! event_handler_function {
+    ( $( $name:ident, $event:expr, $variant:ident($data:ty) ),* ) => {
+        event_handler_function!{@gen $([ $name, $event, $variant, $data ])*}
+    };
+
+    (@gen $( [ $name:ident, $event:expr, $variant:ident, $data:ty ] ), *) => {
+        $(
+            #[doc = concat!("Listens for the `", stringify!($event), "` event, with its [`", stringify!($data), "`]")]
+            ///
+            /// If the data of the event could not be parsed, the raw event handlers are called instead.
+            pub fn $name<F>(&self, handler: F) -> EventCallbackHandle
+                where F: Fn($data) + 'static + Send + Sync
+            {
+                self.on_event($event, move |ctx: EventContext| {
+                    if let EventData::$variant(data) = ctx.event {
+                        handler(data);
+                    }
+                })
+            }
+        )*
+    }
+}
+
 macro_rules! builder {
-    [ @st ( $name:ident $field:tt: $type:tt alias = $alias:tt, $($rest:tt)* ) -> ( $($out:tt)* ) ] => {
-        builder![ @st
+    [ @st $derive:tt ( $name:ident $field:tt: $type:tt alias = $alias:tt, $($rest:tt)* ) -> ( $($out:tt)* ) ] => {
+        builder![ @st $derive
             ( $name $($rest)* ) -> (
                 $($out)*
                 #[doc = concat!("Optional " , stringify!($field), " field")]
@@ -87,26 +110,26 @@ macro_rules! builder {
         ];
     };
 
-    [ @st ( $name:ident $field:tt: $type:tt func, $($rest:tt)* ) -> ( $($out:tt)* ) ] => {
-        builder![ @st ( $name $field: $type, $($rest)* ) -> ( $($out)* ) ];
+    [ @st $derive:tt ( $name:ident $field:tt: $type:tt func, $($rest:tt)* ) -> ( $($out:tt)* ) ] => {
+        builder![ @st $derive ( $name $field: $type, $($rest)* ) -> ( $($out)* ) ];
     };
 
     // TODO: Make this more applicable for other types than just buttons
     // Currently the implementation here only works for buttons, thanks to the deserialize_with attribute
-    [ @st ( $name:ident $field:ident: $type:ty as array, $($rest:tt)* ) -> ( $($out:tt)* ) ] => {
-        builder![ @st
+    [ @st $derive:tt ( $name:ident $field:ident: $type:ty as array, $($rest:tt)* ) -> ( $($out:tt)* ) ] => {
+        builder![ @st $derive
             ( $name $($rest)* ) -> (
                 $($out)*
                 #[doc = concat!("Optional ", stringify!($field), " field")]
-                #[serde(skip_serializing_if = "Vec::is_empty", deserialize_with = "serialize_activity_button")]
+                #[serde(default, skip_serializing_if = "Vec::is_empty", deserialize_with = "serialize_activity_button")]
                 pub $field: Vec<$type>,
             )
         ];
     };
 
 
-    [ @st ( $name:ident $field:ident: $type:ty, $($rest:tt)* ) -> ( $($out:tt)* ) ] => {
-        builder![ @st
+    [ @st $derive:tt ( $name:ident $field:ident: $type:ty, $($rest:tt)* ) -> ( $($out:tt)* ) ] => {
+        builder![ @st $derive
             ( $name $($rest)* ) -> (
                 $($out)*
                 #[doc = concat!("Optional " , stringify!($field), " field")]
@@ -116,9 +139,9 @@ macro_rules! builder {
         ];
     };
 
-    [ @st ( $name:ident ) -> ( $($out:tt)* ) ] => {
+    [ @st $derive:tt ( $name:ident ) -> ( $($out:tt)* ) ] => {
         #[doc = concat!(stringify!($name), " struct")]
-        #[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, Hash, Eq)]
+        #[derive $derive]
         pub struct $name { $($out)* }
     };
 
@@ -150,8 +173,16 @@ macro_rules! builder {
         }
     };
 
-    [ $name:ident $($body:tt)* ] => {
-        builder![@st ( $name $($body)* ) -> () ];
+    // For structs that cannot derive everything, e.g. because they hold floats
+    [ #[derive $derive:tt] $name:ident $($body:tt)* ] => {
+        builder![@st $derive ( $name $($body)* ) -> () ];
         builder![@im ( $name $($body)* ) -> () ];
+    };
+
+    [ $name:ident $($body:tt)* ] => {
+        builder![
+            #[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize, Hash, Eq)]
+            $name $($body)*
+        ];
     }
 }
@@ -3,8 +3,101 @@ use super::events::PartialUser;
 builder! {SubscriptionArgs
     secret: String,     // Activity{Join,Spectate}
     user: PartialUser,  // ActivityJoinRequest
+    guild_id: String,   // GuildStatus
+    channel_id: String, // VoiceState{Create,Update,Delete}, Speaking{Start,Stop}, Message{Create,Update,Delete}
 }
 
 builder! {Subscription
     evt: String,
 }
+
+/// Args to authorize the application, see [`Command::Authorize`](super::Command::Authorize)
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct AuthorizeArgs {
+    /// The `OAuth2` application to authorize
+    pub client_id: String,
+    /// The `OAuth2` scopes to request, e.g. `rpc` or `identify`
+    pub scopes: Vec<String>,
+    /// A token from `/oauth2/token/rpc`, for applications whitelisted for RPC only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_token: Option<String>,
+    /// A username to create a guest account with, if the user has no Discord account
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+}
+
+impl AuthorizeArgs {
+    #[must_use]
+    /// Create a new `AuthorizeArgs` requesting the given scopes
+    pub fn new<I, S>(client_id: u64, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            client_id: client_id.to_string(),
+            scopes: scopes.into_iter().map(Into::into).collect(),
+            rpc_token: None,
+            username: None,
+        }
+    }
+}
+
+/// The response to [`Command::Authorize`](super::Command::Authorize)
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct AuthorizeResponse {
+    /// The `OAuth2` authorization code, to exchange for an access token
+    pub code: String,
+}
+
+/// Args to authenticate the client, see [`Command::Authenticate`](super::Command::Authenticate)
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct AuthenticateArgs {
+    /// The `OAuth2` access token, exchanged for the code from [`AuthorizeResponse`]
+    pub access_token: String,
+}
+
+impl AuthenticateArgs {
+    #[must_use]
+    /// Create a new `AuthenticateArgs`
+    pub fn new<S>(access_token: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            access_token: access_token.into(),
+        }
+    }
+}
+
+/// The response to [`Command::Authenticate`](super::Command::Authenticate)
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct AuthenticateResponse {
+    /// The authenticated user
+    pub user: PartialUser,
+    /// The scopes the access token was authorized for
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// When the access token expires, as an ISO 8601 timestamp
+    pub expires: String,
+    /// The application the access token was authorized for
+    pub application: OAuth2Application,
+}
+
+/// An `OAuth2` application, as returned by [`Command::Authenticate`](super::Command::Authenticate)
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct OAuth2Application {
+    /// The application id
+    pub id: String,
+    /// The application name
+    pub name: String,
+    /// The application description
+    #[serde(default)]
+    pub description: String,
+    /// The hash of the application icon
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// The origins allowed to connect over RPC
+    #[serde(default)]
+    pub rpc_origins: Vec<String>,
+}
@@ -1,3 +1,9 @@
+use super::{
+    guild::{Guild, PartialChannel, PartialGuild, UserVoiceState},
+    rich_presence::Activity,
+};
+use crate::connection::ConnectionState;
+
 builder! {ReadyEvent
     v:      u32,
     config: RpcServerConfiguration,
@@ -21,3 +27,163 @@ builder! {PartialUser
     discriminator: String,
     avatar:        String,
 }
+
+/// Data for the client-side connection events,
+/// [`Event::Connected`](super::Event::Connected), [`Event::Disconnected`](super::Event::Disconnected)
+/// and [`Event::ConnectionStateChanged`](super::Event::ConnectionStateChanged)
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Copy, Clone, Hash)]
+pub struct ConnectionStateEvent {
+    /// The state the connection was in before
+    pub previous: ConnectionState,
+    /// The state the connection is in now
+    pub current: ConnectionState,
+}
+
+/// Data for the client-side [`Event::ReconnectFailed`](super::Event::ReconnectFailed) event
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct ReconnectFailedEvent {
+    /// How many consecutive attempts failed
+    pub attempts: u32,
+    /// The error from the last attempt
+    pub message: String,
+}
+
+/// Data for the [`Event::VoiceStateCreate`](super::Event::VoiceStateCreate),
+/// [`Event::VoiceStateUpdate`](super::Event::VoiceStateUpdate)
+/// and [`Event::VoiceStateDelete`](super::Event::VoiceStateDelete) events
+pub type VoiceStateEvent = UserVoiceState;
+
+/// Data for the [`Event::GuildCreate`](super::Event::GuildCreate) event
+pub type GuildCreateEvent = PartialGuild;
+
+/// Data for the [`Event::ChannelCreate`](super::Event::ChannelCreate) event
+pub type ChannelCreateEvent = PartialChannel;
+
+/// The latest pings to the voice server, in milliseconds
+pub type Pings = Vec<f64>;
+
+/// Users mentioned in a message
+pub type PartialUsers = Vec<PartialUser>;
+
+builder! {SpeakingEvent
+    user_id: String,
+}
+
+// Floats are neither `Eq` nor `Hash`
+builder! {#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)] VoiceConnectionStatusEvent
+    // e.g. `VOICE_CONNECTING`, `VOICE_CONNECTED` or `DISCONNECTED`
+    state: String,
+    hostname: String,
+    pings: Pings,
+    average_ping: f64,
+    last_ping: f64,
+}
+
+builder! {MessageEvent
+    channel_id: String,
+    // Only has an id for `MESSAGE_DELETE`
+    message: ChannelMessage func,
+}
+
+builder! {ChannelMessage
+    id: String,
+    content: String,
+    author: PartialUser func,
+    nick: String,
+    author_color: String,
+    timestamp: String,
+    edited_timestamp: String,
+    tts: bool,
+    pinned: bool,
+    blocked: bool,
+    bot: bool,
+    mentions: PartialUsers,
+    kind: u8 alias = "type",
+}
+
+builder! {GuildStatusEvent
+    guild: Guild func,
+    // Deprecated, always 0
+    online: u32,
+}
+
+builder! {NotificationCreateEvent
+    channel_id: String,
+    message: ChannelMessage func,
+    icon_url: String,
+    title: String,
+    body: String,
+}
+
+builder! {ActivityInviteEvent
+    user: PartialUser func,
+    activity: Activity func,
+    // 1 for join invites, 3 for listening along
+    kind: u8 alias = "type",
+    channel_id: String,
+    message_id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Event, EventData};
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_discord_events() {
+        let message = Event::MessageCreate.parse_data(json!({
+            "channel_id": "199737254929760256",
+            "message": {
+                "id": "199743874640379904",
+                "content": "test",
+                "author": { "id": "190320984123768832", "username": "test 2", "discriminator": "7479", "avatar": null },
+                "timestamp": "2016-07-05T04:30:50.776Z",
+                "edited_timestamp": null,
+                "tts": false,
+                "mentions": [],
+                "pinned": false,
+                "type": 0
+            }
+        }));
+        let EventData::Message(message) = message else {
+            panic!("unexpected event data: {:?}", message);
+        };
+        let message = message.message.unwrap();
+        assert_eq!(message.content.as_deref(), Some("test"));
+        assert_eq!(message.author.unwrap().username.as_deref(), Some("test 2"));
+
+        assert_eq!(
+            Event::SpeakingStart.parse_data(json!({ "user_id": "1" })),
+            EventData::Speaking(SpeakingEvent::new().user_id("1"))
+        );
+        assert!(matches!(
+            Event::VoiceStateUpdate.parse_data(json!({
+                "voice_state": { "mute": false, "deaf": false, "self_mute": false, "self_deaf": true, "suppress": false },
+                "user": { "id": "1", "username": "test" },
+                "volume": 100,
+                "mute": false
+            })),
+            EventData::VoiceState(state) if state.voice_state.self_deaf
+        ));
+        assert!(matches!(
+            Event::VoiceConnectionStatus.parse_data(json!({
+                "state": "VOICE_CONNECTED",
+                "hostname": "some-server.discord.gg",
+                "pings": [20, 13.5],
+                "average_ping": 16.75,
+                "last_ping": 13.5
+            })),
+            EventData::VoiceConnectionStatus(status) if status.pings == Some(vec![20.0, 13.5])
+        ));
+        // Activities without buttons parse too
+        assert!(matches!(
+            Event::ActivityInvite.parse_data(json!({
+                "user": { "id": "1" },
+                "activity": { "state": "rusting" },
+                "type": 1
+            })),
+            EventData::ActivityInvite(invite) if invite.activity.as_ref().and_then(|a| a.state.as_deref()) == Some("rusting")
+        ));
+    }
+}
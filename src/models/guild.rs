@@ -0,0 +1,275 @@
+use super::events::{ChannelMessage, PartialUser};
+
+/// Args to get a guild, see [`Command::GetGuild`](super::Command::GetGuild)
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct GetGuildArgs {
+    /// The guild to get
+    pub guild_id: String,
+    /// How long Discord waits for the guild to become available, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+}
+
+impl GetGuildArgs {
+    #[must_use]
+    /// Create a new `GetGuildArgs`
+    pub fn new(guild_id: u64) -> Self {
+        Self {
+            guild_id: guild_id.to_string(),
+            timeout: None,
+        }
+    }
+}
+
+/// Args to get a channel, see [`Command::GetChannel`](super::Command::GetChannel)
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct GetChannelArgs {
+    /// The channel to get
+    pub channel_id: String,
+}
+
+impl GetChannelArgs {
+    #[must_use]
+    /// Create a new `GetChannelArgs`
+    pub fn new(channel_id: u64) -> Self {
+        Self {
+            channel_id: channel_id.to_string(),
+        }
+    }
+}
+
+/// Args to get the channels of a guild, see [`Command::GetChannels`](super::Command::GetChannels)
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct GetChannelsArgs {
+    /// The guild to get the channels of
+    pub guild_id: String,
+}
+
+impl GetChannelsArgs {
+    #[must_use]
+    /// Create a new `GetChannelsArgs`
+    pub fn new(guild_id: u64) -> Self {
+        Self {
+            guild_id: guild_id.to_string(),
+        }
+    }
+}
+
+/// A guild, as returned by [`Command::GetGuild`](super::Command::GetGuild)
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct Guild {
+    /// The guild id
+    pub id: String,
+    /// The guild name
+    pub name: String,
+    /// The URL of the guild icon
+    #[serde(default)]
+    pub icon_url: Option<String>,
+    /// The members of the guild
+    ///
+    /// Discord no longer sends these, so this is usually empty.
+    #[serde(default)]
+    pub members: Vec<PartialMember>,
+}
+
+/// A guild, as listed by [`Command::GetGuilds`](super::Command::GetGuilds)
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct PartialGuild {
+    /// The guild id
+    pub id: String,
+    /// The guild name
+    pub name: String,
+}
+
+/// The response to [`Command::GetGuilds`](super::Command::GetGuilds)
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct GetGuildsResponse {
+    /// The guilds the user is in
+    pub guilds: Vec<PartialGuild>,
+}
+
+/// A member of a guild
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct PartialMember {
+    /// The user
+    pub user: PartialUser,
+    /// The nickname of the user in the guild
+    #[serde(default)]
+    pub nick: Option<String>,
+}
+
+/// The type of a [`Channel`]
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Copy, Clone, Hash)]
+#[serde(from = "u8", into = "u8")]
+pub enum ChannelType {
+    /// A text channel in a guild
+    #[default]
+    GuildText,
+    /// A direct message
+    Dm,
+    /// A voice channel in a guild
+    GuildVoice,
+    /// A direct message between multiple users
+    GroupDm,
+    /// A category of channels in a guild
+    GuildCategory,
+    /// A channel type this crate does not know about yet
+    Unknown(u8),
+}
+
+impl From<u8> for ChannelType {
+    fn from(kind: u8) -> Self {
+        match kind {
+            0 => Self::GuildText,
+            1 => Self::Dm,
+            2 => Self::GuildVoice,
+            3 => Self::GroupDm,
+            4 => Self::GuildCategory,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl From<ChannelType> for u8 {
+    fn from(kind: ChannelType) -> Self {
+        match kind {
+            ChannelType::GuildText => 0,
+            ChannelType::Dm => 1,
+            ChannelType::GuildVoice => 2,
+            ChannelType::GroupDm => 3,
+            ChannelType::GuildCategory => 4,
+            ChannelType::Unknown(other) => other,
+        }
+    }
+}
+
+/// A channel, as returned by [`Command::GetChannel`](super::Command::GetChannel)
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize, Clone)]
+pub struct Channel {
+    /// The channel id
+    pub id: String,
+    /// The guild the channel is in, if any
+    #[serde(default)]
+    pub guild_id: Option<String>,
+    /// The channel name
+    #[serde(default)]
+    pub name: String,
+    /// The channel type
+    #[serde(rename = "type")]
+    pub kind: ChannelType,
+    /// The channel topic, for text channels
+    #[serde(default)]
+    pub topic: Option<String>,
+    /// The bitrate, for voice channels
+    #[serde(default)]
+    pub bitrate: Option<u32>,
+    /// The maximum number of users, for voice channels, where 0 means unlimited
+    #[serde(default)]
+    pub user_limit: Option<u32>,
+    /// The position of the channel in the guild
+    #[serde(default)]
+    pub position: Option<u32>,
+    /// The voice states of the users in the channel, for voice channels
+    #[serde(default)]
+    pub voice_states: Vec<UserVoiceState>,
+    /// The latest messages in the channel, for text channels
+    #[serde(default)]
+    pub messages: Vec<ChannelMessage>,
+}
+
+/// A channel, as listed by [`Command::GetChannels`](super::Command::GetChannels)
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct PartialChannel {
+    /// The channel id
+    pub id: String,
+    /// The channel name
+    pub name: String,
+    /// The channel type
+    #[serde(rename = "type")]
+    pub kind: ChannelType,
+}
+
+/// The response to [`Command::GetChannels`](super::Command::GetChannels)
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct GetChannelsResponse {
+    /// The channels of the guild
+    pub channels: Vec<PartialChannel>,
+}
+
+/// The voice settings of a user in a voice channel, as seen by the current user
+#[derive(Debug, Default, PartialEq, Deserialize, Serialize, Clone)]
+pub struct UserVoiceState {
+    /// The user
+    pub user: PartialUser,
+    /// The nickname of the user in the guild
+    #[serde(default)]
+    pub nick: Option<String>,
+    /// Whether the current user muted this user locally
+    #[serde(default)]
+    pub mute: bool,
+    /// The local volume of this user, from 0 to 200
+    #[serde(default)]
+    pub volume: Option<f32>,
+    /// The state of the user in the channel
+    #[serde(default)]
+    pub voice_state: VoiceState,
+}
+
+/// The state of a user in a voice channel
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Copy, Clone, Hash)]
+#[serde(default)]
+// The flags mirror Discord's voice state object
+#[allow(clippy::struct_excessive_bools)]
+pub struct VoiceState {
+    /// Whether the user is muted by the guild
+    pub mute: bool,
+    /// Whether the user is deafened by the guild
+    pub deaf: bool,
+    /// Whether the user muted themselves
+    pub self_mute: bool,
+    /// Whether the user deafened themselves
+    pub self_deaf: bool,
+    /// Whether the user is suppressed, e.g. in a channel they cannot speak in
+    pub suppress: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_channels() {
+        let channel: Channel = serde_json::from_value(serde_json::json!({
+            "id": "199737254929760256",
+            "name": "General",
+            "type": 2,
+            "bitrate": 64000,
+            "user_limit": 0,
+            "guild_id": "199737254929760256",
+            "position": 0,
+            "voice_states": [{
+                "voice_state": { "mute": false, "deaf": false, "self_mute": true, "self_deaf": false, "suppress": false },
+                "user": { "id": "190320984123768832", "username": "test 2", "discriminator": "7479", "avatar": "b004ec1740a63ca06ae2e14c5cee11f3" },
+                "nick": "test user 2",
+                "volume": 110,
+                "mute": false,
+                "pan": { "left": 1.0, "right": 1.0 }
+            }]
+        }))
+        .unwrap();
+
+        assert_eq!(channel.kind, ChannelType::GuildVoice);
+        assert_eq!(channel.voice_states[0].nick.as_deref(), Some("test user 2"));
+        assert!(channel.voice_states[0].voice_state.self_mute);
+
+        let channels: GetChannelsResponse = serde_json::from_value(serde_json::json!({
+            "channels": [{ "id": "1", "name": "news", "type": 5 }]
+        }))
+        .unwrap();
+        assert_eq!(channels.channels[0].kind, ChannelType::Unknown(5));
+        assert_eq!(
+            serde_json::to_value(&channels.channels[0]).unwrap()["type"],
+            5
+        );
+    }
+}
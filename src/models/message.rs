@@ -1,12 +1,13 @@
 use crate::{DiscordError, Result};
-use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
+use bytes::{Buf, BytesMut};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use serde::Serialize;
-use std::io::{Read, Write};
+use std::io::Write;
 
 /// Codes for payload types
-#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, FromPrimitive, Deserialize, Serialize)]
 #[repr(u32)]
 pub enum OpCode {
     /// Handshake payload
@@ -21,6 +22,12 @@ pub enum OpCode {
     Pong,
 }
 
+/// Length of the header before each frame, the opcode and payload length as little-endian `u32`s
+pub const HEADER_LEN: usize = 8;
+
+/// Largest payload accepted from Discord, as a corrupt header could otherwise make the client buffer without bound
+pub const MAX_PAYLOAD_LEN: usize = 1024 * 1024;
+
 /// Message struct for the Discord RPC
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Message {
@@ -67,17 +74,56 @@ impl Message {
 
     /// Decode message
     ///
+    /// Only the payload length declared in the header is read, anything after it is ignored.
+    ///
     /// # Errors
     /// - Failed to read from buffer
+    /// - The buffer is shorter than the declared payload length
+    /// - The opcode is unknown, or the payload is not valid UTF-8
     pub fn decode(mut bytes: &[u8]) -> Result<Self> {
         let opcode =
             OpCode::from_u32(bytes.read_u32::<LittleEndian>()?).ok_or(DiscordError::Conversion)?;
         let len = bytes.read_u32::<LittleEndian>()? as usize;
-        let mut payload = String::with_capacity(len);
-        bytes.read_to_string(&mut payload)?;
+
+        if bytes.len() < len {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+
+        let payload = std::str::from_utf8(&bytes[..len])
+            .map_err(|_| DiscordError::Conversion)?
+            .to_owned();
 
         Ok(Self { opcode, payload })
     }
+
+    /// Decode the first complete frame in `buf`, removing it from the buffer
+    ///
+    /// Returns `None`, leaving the buffer untouched, if it does not hold a complete frame yet.
+    ///
+    /// # Errors
+    /// - The frame could not be decoded. It is still removed from the buffer.
+    /// - [`DiscordError::FrameTooLarge`] if the payload is longer than [`MAX_PAYLOAD_LEN`].
+    ///   The buffer is cleared, as the next frame cannot be found anymore.
+    pub fn decode_frame(buf: &mut BytesMut) -> Result<Option<Self>> {
+        if buf.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let len = LittleEndian::read_u32(&buf[4..HEADER_LEN]) as usize;
+
+        if len > MAX_PAYLOAD_LEN {
+            buf.clear();
+            return Err(DiscordError::FrameTooLarge(len));
+        }
+
+        if buf.len() < HEADER_LEN + len {
+            return Ok(None);
+        }
+
+        let frame = buf.split_to(HEADER_LEN + len);
+
+        Self::decode(frame.chunk()).map(Some)
+    }
 }
 
 #[cfg(test)]
@@ -98,6 +144,124 @@ mod tests {
         assert_eq!(msg, decoded);
     }
 
+    fn frames() -> Vec<Message> {
+        vec![
+            Message::new(OpCode::Frame, Something { empty: true }).unwrap(),
+            Message::new(OpCode::Ping, "x".repeat(3000)).unwrap(),
+            Message::new(OpCode::Frame, Something { empty: false }).unwrap(),
+        ]
+    }
+
+    fn encode_all(messages: &[Message]) -> Vec<u8> {
+        messages.iter().flat_map(|m| m.encode().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_decode_ignores_trailing_bytes() {
+        let bytes = encode_all(&frames());
+        let decoded = Message::decode(&bytes).expect("Failed to decode message");
+        assert_eq!(decoded, frames()[0]);
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        let bytes = frames()[1].encode().unwrap();
+        assert!(Message::decode(&bytes[..100]).is_err());
+    }
+
+    #[test]
+    fn test_decode_coalesced_frames() {
+        let mut buf = BytesMut::from(&encode_all(&frames())[..]);
+
+        for expected in frames() {
+            assert_eq!(Message::decode_frame(&mut buf).unwrap(), Some(expected));
+        }
+        assert_eq!(Message::decode_frame(&mut buf).unwrap(), None);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode_split_frames() {
+        let expected = frames();
+        let bytes = encode_all(&expected);
+
+        // Split the stream at every possible position
+        for split in 0..bytes.len() {
+            let mut buf = BytesMut::new();
+            let mut decoded = vec![];
+
+            for chunk in [&bytes[..split], &bytes[split..]] {
+                buf.extend_from_slice(chunk);
+                while let Some(message) = Message::decode_frame(&mut buf).unwrap() {
+                    decoded.push(message);
+                }
+            }
+
+            assert_eq!(decoded, expected, "split at {split}");
+        }
+    }
+
+    #[test]
+    fn test_decode_rejects_oversized_frames() {
+        let mut buf = BytesMut::new();
+        buf.extend_from_slice(&1u32.to_le_bytes());
+        buf.extend_from_slice(&u32::MAX.to_le_bytes());
+        buf.extend_from_slice(b"{}");
+
+        // Fails right away, rather than waiting for 4 GiB to arrive
+        assert!(matches!(
+            Message::decode_frame(&mut buf),
+            Err(DiscordError::FrameTooLarge(len)) if len == u32::MAX as usize
+        ));
+        assert!(buf.is_empty());
+
+        let largest = Message::new(OpCode::Frame, "x".repeat(MAX_PAYLOAD_LEN - 2)).unwrap();
+        let mut buf = BytesMut::from(&largest.encode().unwrap()[..]);
+        assert_eq!(Message::decode_frame(&mut buf).unwrap(), Some(largest));
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn fuzz_decode_random_chunks() {
+        let expected: Vec<Message> = (0..20).flat_map(|_| frames()).collect();
+        let bytes = encode_all(&expected);
+
+        for _ in 0..200 {
+            let mut buf = BytesMut::new();
+            let mut decoded = vec![];
+            let mut rest = &bytes[..];
+
+            while !rest.is_empty() {
+                let len = 1 + crate::utils::random_below(2048) as usize;
+                let (chunk, tail) = rest.split_at(len.min(rest.len()));
+                rest = tail;
+
+                buf.extend_from_slice(chunk);
+                while let Some(message) = Message::decode_frame(&mut buf).unwrap() {
+                    decoded.push(message);
+                }
+            }
+
+            assert_eq!(decoded, expected);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    #[allow(clippy::cast_possible_truncation)]
+    fn fuzz_decode_garbage() {
+        for _ in 0..1000 {
+            let len = crate::utils::random_below(64) as usize;
+            let garbage: Vec<u8> = (0..len)
+                .map(|_| crate::utils::random_below(256) as u8)
+                .collect();
+
+            // Must never panic, whatever the input
+            let _ = Message::decode(&garbage);
+            let _ = Message::decode_frame(&mut BytesMut::from(&garbage[..]));
+        }
+    }
+
     #[test]
     fn test_opcode() {
         assert_eq!(OpCode::from_u32(0), Some(OpCode::Handshake));
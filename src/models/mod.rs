@@ -2,23 +2,29 @@
 pub mod commands;
 /// The events module
 pub mod events;
+/// The guild and channel module
+pub mod guild;
 /// The module to handle messages
 pub mod message;
 /// The module to handle payloads
 pub mod payload;
 /// The rich presence module
 pub mod rich_presence;
+/// The voice module
+pub mod voice;
 
 use quork::traits::list::ListVariants;
 
 /// Different Discord commands
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Deserialize, Serialize, Hash)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Command {
     /// Dispatch something to Discord
     Dispatch,
     /// Authorize connection
     Authorize,
+    /// Authenticate with an `OAuth2` access token
+    Authenticate,
     /// Subscribe to an event
     Subscribe,
     /// Unsubscribe from Discord
@@ -29,11 +35,30 @@ pub enum Command {
     SendActivityJoinInvite,
     /// Close the invite to join a game
     CloseActivityRequest,
+    /// Get a guild the user is in
+    GetGuild,
+    /// Get the guilds the user is in
+    GetGuilds,
+    /// Get a channel the user can see
+    GetChannel,
+    /// Get the channels of a guild
+    GetChannels,
+    /// Join or leave a voice channel
+    SelectVoiceChannel,
+    /// Get the voice channel the user is in
+    GetSelectedVoiceChannel,
+    /// Join or leave a text channel
+    SelectTextChannel,
+    /// Get the voice settings of the user
+    GetVoiceSettings,
+    /// Change the voice settings of the user
+    SetVoiceSettings,
+    /// Change how the user hears another user
+    SetUserVoiceSettings,
 }
 
-// NOTE: ListVariants is required to bevy-discord-rpc
 /// Discord events
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Copy, Clone, Hash, ListVariants)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Copy, Clone, Hash)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Event {
     /// Ready event, fired when the client is ready, but not if an error occurs
@@ -46,9 +71,96 @@ pub enum Event {
     ActivitySpectate,
     /// ActivityJoinRequest event, fired when the client receives a join request
     ActivityJoinRequest,
+    /// `ActivityInvite` event, fired when the user receives an invite to an activity
+    ActivityInvite,
+    /// `VoiceStateCreate` event, fired when a user joins a subscribed voice channel
+    VoiceStateCreate,
+    /// `VoiceStateUpdate` event, fired when the state of a user in a subscribed voice channel changes
+    VoiceStateUpdate,
+    /// `VoiceStateDelete` event, fired when a user leaves a subscribed voice channel
+    VoiceStateDelete,
+    /// `SpeakingStart` event, fired when a user in a subscribed voice channel starts speaking
+    SpeakingStart,
+    /// `SpeakingStop` event, fired when a user in a subscribed voice channel stops speaking
+    SpeakingStop,
+    /// `VoiceConnectionStatus` event, fired when the user's voice connection status changes
+    VoiceConnectionStatus,
+    /// `MessageCreate` event, fired when a message is sent in a subscribed text channel
+    MessageCreate,
+    /// `MessageUpdate` event, fired when a message is edited in a subscribed text channel
+    MessageUpdate,
+    /// `MessageDelete` event, fired when a message is deleted in a subscribed text channel
+    MessageDelete,
+    /// `GuildStatus` event, fired when a subscribed guild changes
+    GuildStatus,
+    /// `GuildCreate` event, fired when the user joins a guild
+    GuildCreate,
+    /// `ChannelCreate` event, fired when a channel the user can see is created
+    ChannelCreate,
+    /// `NotificationCreate` event, fired when the user receives a notification
+    NotificationCreate,
+    /// Connected event, fired by the client itself whenever it (re)connects to Discord
+    ///
+    /// Client-side only, it cannot be subscribed to.
+    #[serde(skip)]
+    Connected,
+    /// Disconnected event, fired by the client itself whenever it loses its connection to Discord
+    ///
+    /// Client-side only, it cannot be subscribed to.
+    #[serde(skip)]
+    Disconnected,
+    /// `ConnectionStateChanged` event, fired by the client itself on every [`ConnectionState`](crate::ConnectionState) transition
+    ///
+    /// Client-side only, it cannot be subscribed to.
+    #[serde(skip)]
+    ConnectionStateChanged,
+    /// `ReconnectFailed` event, fired by the client itself when its [`ReconnectPolicy`](crate::ReconnectPolicy) gives up
+    ///
+    /// The client stops once this is fired.
+    /// Client-side only, it cannot be subscribed to.
+    #[serde(skip)]
+    ReconnectFailed,
+}
+
+// NOTE: ListVariants is required to bevy-discord-rpc
+// Implemented by hand to leave out the client-side events, which Discord does not know about
+impl ListVariants<19> for Event {
+    const VARIANTS: [Self; 19] = [
+        Event::Ready,
+        Event::Error,
+        Event::ActivityJoin,
+        Event::ActivitySpectate,
+        Event::ActivityJoinRequest,
+        Event::ActivityInvite,
+        Event::VoiceStateCreate,
+        Event::VoiceStateUpdate,
+        Event::VoiceStateDelete,
+        Event::SpeakingStart,
+        Event::SpeakingStop,
+        Event::VoiceConnectionStatus,
+        Event::MessageCreate,
+        Event::MessageUpdate,
+        Event::MessageDelete,
+        Event::GuildStatus,
+        Event::GuildCreate,
+        Event::ChannelCreate,
+        Event::NotificationCreate,
+    ];
 }
 
 impl Event {
+    /// The events that can be subscribed to without [authenticating](crate::Client::authenticate)
+    ///
+    /// These were the only variants of `ListVariants` before the voice, message, guild and notification events,
+    /// which need the `rpc` scope.
+    pub const WITHOUT_AUTH: [Self; 5] = [
+        Event::Ready,
+        Event::Error,
+        Event::ActivityJoin,
+        Event::ActivitySpectate,
+        Event::ActivityJoinRequest,
+    ];
+
     #[must_use]
     /// Parse event data from a [`JsonValue`]
     pub fn parse_data(self, data: JsonValue) -> EventData {
@@ -72,11 +184,61 @@ impl Event {
             Event::ActivityJoinRequest => serde_json::from_value(data.clone())
                 .map(EventData::ActivityJoinRequest)
                 .unwrap_or(EventData::Unknown(data)),
+
+            Event::ActivityInvite => serde_json::from_value(data.clone())
+                .map(EventData::ActivityInvite)
+                .unwrap_or(EventData::Unknown(data)),
+
+            Event::VoiceStateCreate | Event::VoiceStateUpdate | Event::VoiceStateDelete => {
+                serde_json::from_value(data.clone())
+                    .map(EventData::VoiceState)
+                    .unwrap_or(EventData::Unknown(data))
+            }
+
+            Event::SpeakingStart | Event::SpeakingStop => serde_json::from_value(data.clone())
+                .map(EventData::Speaking)
+                .unwrap_or(EventData::Unknown(data)),
+
+            Event::VoiceConnectionStatus => serde_json::from_value(data.clone())
+                .map(EventData::VoiceConnectionStatus)
+                .unwrap_or(EventData::Unknown(data)),
+
+            Event::MessageCreate | Event::MessageUpdate | Event::MessageDelete => {
+                serde_json::from_value(data.clone())
+                    .map(EventData::Message)
+                    .unwrap_or(EventData::Unknown(data))
+            }
+
+            Event::GuildStatus => serde_json::from_value(data.clone())
+                .map(EventData::GuildStatus)
+                .unwrap_or(EventData::Unknown(data)),
+
+            Event::GuildCreate => serde_json::from_value(data.clone())
+                .map(EventData::GuildCreate)
+                .unwrap_or(EventData::Unknown(data)),
+
+            Event::ChannelCreate => serde_json::from_value(data.clone())
+                .map(EventData::ChannelCreate)
+                .unwrap_or(EventData::Unknown(data)),
+
+            Event::NotificationCreate => serde_json::from_value(data.clone())
+                .map(EventData::NotificationCreate)
+                .unwrap_or(EventData::Unknown(data)),
+
+            Event::Connected | Event::Disconnected | Event::ConnectionStateChanged => {
+                serde_json::from_value(data.clone())
+                    .map(EventData::ConnectionState)
+                    .unwrap_or(EventData::Unknown(data))
+            }
+
+            Event::ReconnectFailed => serde_json::from_value(data.clone())
+                .map(EventData::ReconnectFailed)
+                .unwrap_or(EventData::Unknown(data)),
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
+#[derive(Debug, PartialEq, Deserialize, Serialize, Clone)]
 /// Internal data for the [`Event`] enum
 pub enum EventData {
     /// Ready event data
@@ -89,25 +251,100 @@ pub enum EventData {
     ActivitySpectate(ActivitySpectateEvent),
     /// ActivityJoinRequest event data
     ActivityJoinRequest(ActivityJoinRequestEvent),
+    /// `ActivityInvite` event data
+    ActivityInvite(ActivityInviteEvent),
+    /// `VoiceStateCreate`, `VoiceStateUpdate` and `VoiceStateDelete` event data
+    VoiceState(VoiceStateEvent),
+    /// `SpeakingStart` and `SpeakingStop` event data
+    Speaking(SpeakingEvent),
+    /// `VoiceConnectionStatus` event data
+    VoiceConnectionStatus(VoiceConnectionStatusEvent),
+    /// `MessageCreate`, `MessageUpdate` and `MessageDelete` event data
+    Message(MessageEvent),
+    /// `GuildStatus` event data
+    GuildStatus(GuildStatusEvent),
+    /// `GuildCreate` event data
+    GuildCreate(GuildCreateEvent),
+    /// `ChannelCreate` event data
+    ChannelCreate(ChannelCreateEvent),
+    /// `NotificationCreate` event data
+    NotificationCreate(NotificationCreateEvent),
+    /// Connected, Disconnected and `ConnectionStateChanged` event data
+    ConnectionState(ConnectionStateEvent),
+    /// `ReconnectFailed` event data
+    ReconnectFailed(ReconnectFailedEvent),
     /// Unknown event data
     Unknown(JsonValue),
 }
 
 pub use commands::*;
 pub use events::*;
+pub use guild::*;
 pub use message::{Message, OpCode};
 
 pub use rich_presence::*;
 use serde_json::Value as JsonValue;
+pub use voice::*;
 
 /// Prelude for all Discord RPC types
 pub mod prelude {
-    pub use super::commands::{Subscription, SubscriptionArgs};
-    pub use super::events::{ErrorEvent, ReadyEvent};
+    pub use super::commands::{
+        AuthenticateArgs, AuthenticateResponse, AuthorizeArgs, AuthorizeResponse,
+        OAuth2Application, Subscription, SubscriptionArgs,
+    };
+    pub use super::events::{
+        ActivityInviteEvent, ChannelCreateEvent, ChannelMessage, ConnectionStateEvent, ErrorEvent,
+        GuildCreateEvent, GuildStatusEvent, MessageEvent, NotificationCreateEvent, ReadyEvent,
+        ReconnectFailedEvent, SpeakingEvent, VoiceConnectionStatusEvent, VoiceStateEvent,
+    };
+    pub use super::guild::{
+        Channel, ChannelType, GetChannelArgs, GetChannelsArgs, GetGuildArgs, Guild, PartialChannel,
+        PartialGuild, PartialMember, UserVoiceState, VoiceState,
+    };
     pub use super::rich_presence::{
         ActivityJoinEvent, ActivityJoinRequestEvent, ActivitySpectateEvent,
         CloseActivityRequestArgs, SendActivityJoinInviteArgs, SetActivityArgs,
     };
+    pub use super::voice::{
+        Pan, SelectTextChannelArgs, SelectVoiceChannelArgs, ShortcutKeyCombo, UserVoiceSettings,
+        VoiceDevice, VoiceInputOutput, VoiceMode, VoiceModeType, VoiceSettings,
+    };
     pub use super::Command;
     pub use super::Event;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_side_events_are_not_sent_to_discord() {
+        let client_side = [
+            Event::Connected,
+            Event::Disconnected,
+            Event::ConnectionStateChanged,
+            Event::ReconnectFailed,
+        ];
+
+        for event in client_side {
+            assert!(!Event::VARIANTS.contains(&event), "{:?} is listed", event);
+            assert!(serde_json::to_string(&event).is_err());
+        }
+        assert!(serde_json::from_str::<Event>(r#""CONNECTED""#).is_err());
+        assert_eq!(
+            serde_json::to_string(&Event::NotificationCreate).unwrap(),
+            r#""NOTIFICATION_CREATE""#
+        );
+    }
+
+    #[test]
+    fn events_without_auth_are_listed() {
+        for event in Event::WITHOUT_AUTH {
+            assert!(
+                Event::VARIANTS.contains(&event),
+                "{:?} is not listed",
+                event
+            );
+        }
+    }
+}
@@ -3,7 +3,7 @@ use crate::utils;
 use serde::{de::DeserializeOwned, Serialize};
 
 /// The Discord client payload
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
 pub struct Payload<T>
 where
     T: Serialize,
@@ -6,7 +6,7 @@ use super::events::PartialUser;
 use crate::utils;
 
 /// Args to set Discord activity
-#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Clone)]
 pub struct SetActivityArgs {
     pid: u32,
 
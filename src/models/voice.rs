@@ -0,0 +1,188 @@
+/// Args to join or leave a voice channel, see [`Command::SelectVoiceChannel`](super::Command::SelectVoiceChannel)
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct SelectVoiceChannelArgs {
+    /// The channel to join, or `None` to leave the current one
+    pub channel_id: Option<String>,
+    /// How long Discord waits to join the channel, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+    /// Whether to join even if the user is already in another voice channel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub force: Option<bool>,
+    /// Whether the Discord client navigates to the channel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub navigate: Option<bool>,
+}
+
+impl SelectVoiceChannelArgs {
+    #[must_use]
+    /// Create a new `SelectVoiceChannelArgs`, to join `channel_id`, or leave the current channel if `None`
+    pub fn new(channel_id: Option<u64>) -> Self {
+        Self {
+            channel_id: channel_id.map(|id| id.to_string()),
+            ..Self::default()
+        }
+    }
+
+    #[must_use]
+    /// Join even if the user is already in another voice channel
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = Some(force);
+        self
+    }
+
+    #[must_use]
+    /// Navigate the Discord client to the channel
+    pub fn navigate(mut self, navigate: bool) -> Self {
+        self.navigate = Some(navigate);
+        self
+    }
+}
+
+/// Args to join or leave a text channel, see [`Command::SelectTextChannel`](super::Command::SelectTextChannel)
+#[derive(Debug, Default, PartialEq, Eq, Deserialize, Serialize, Clone, Hash)]
+pub struct SelectTextChannelArgs {
+    /// The channel to join, or `None` to leave the current one
+    pub channel_id: Option<String>,
+    /// How long Discord waits to join the channel, in seconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<u32>,
+}
+
+impl SelectTextChannelArgs {
+    #[must_use]
+    /// Create a new `SelectTextChannelArgs`, to join `channel_id`, or leave the current channel if `None`
+    pub fn new(channel_id: Option<u64>) -> Self {
+        Self {
+            channel_id: channel_id.map(|id| id.to_string()),
+            timeout: None,
+        }
+    }
+}
+
+/// How voice is transmitted, see [`VoiceMode`]
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize, Copy, Clone, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum VoiceModeType {
+    /// Transmit while a shortcut is held
+    PushToTalk,
+    /// Transmit whenever the input is loud enough
+    VoiceActivity,
+}
+
+/// The devices available for voice input or output
+pub type VoiceDevices = Vec<VoiceDevice>;
+
+/// The key combination of a push to talk shortcut
+pub type ShortcutKeyCombos = Vec<ShortcutKeyCombo>;
+
+// Floats are neither `Eq` nor `Hash`
+builder! {#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)] VoiceSettings
+    input: VoiceInputOutput func,
+    output: VoiceInputOutput func,
+    mode: VoiceMode func,
+    automatic_gain_control: bool,
+    echo_cancellation: bool,
+    noise_suppression: bool,
+    qos: bool,
+    silence_warning: bool,
+    deaf: bool,
+    mute: bool,
+}
+
+builder! {#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)] VoiceInputOutput
+    device_id: String,
+    // 0 to 100
+    volume: f32,
+    // Only sent by Discord, it cannot be set
+    available_devices: VoiceDevices,
+}
+
+builder! {VoiceDevice
+    id: String,
+    name: String,
+}
+
+builder! {#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)] VoiceMode
+    kind: VoiceModeType alias = "type",
+    auto_threshold: bool,
+    // -100 to 0, in dB
+    threshold: f32,
+    shortcut: ShortcutKeyCombos,
+    // 0 to 2000, in milliseconds
+    delay: f32,
+}
+
+builder! {ShortcutKeyCombo
+    // 0 for keyboard keys, 1 for mouse buttons, 2 for keyboard modifiers, 3 for gamepad buttons
+    kind: u8 alias = "type",
+    code: u32,
+    name: String,
+}
+
+builder! {#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)] UserVoiceSettings
+    user_id: String,
+    pan: Pan func,
+    // 0 to 200
+    volume: u32,
+    mute: bool,
+}
+
+builder! {#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)] Pan
+    // 0 to 1
+    left: f32,
+    // 0 to 1
+    right: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leaving_sends_null_channel() {
+        assert_eq!(
+            serde_json::to_value(SelectVoiceChannelArgs::new(None).force(true)).unwrap(),
+            serde_json::json!({ "channel_id": null, "force": true })
+        );
+    }
+
+    #[test]
+    fn round_trips_voice_settings() {
+        let json = serde_json::json!({
+            "input": { "device_id": "default", "volume": 49.5, "available_devices": [{ "id": "default", "name": "Default" }] },
+            "output": { "device_id": "default", "volume": 93.0 },
+            "mode": {
+                "type": "PUSH_TO_TALK",
+                "auto_threshold": false,
+                "threshold": -46.5,
+                "shortcut": [{ "type": 0, "code": 12, "name": "i" }],
+                "delay": 20.0
+            },
+            "automatic_gain_control": false,
+            "echo_cancellation": false,
+            "noise_suppression": false,
+            "qos": false,
+            "silence_warning": false,
+            "deaf": false,
+            "mute": true
+        });
+
+        let settings: VoiceSettings = serde_json::from_value(json.clone()).unwrap();
+        let mode = settings.mode.as_ref().unwrap();
+        assert_eq!(mode.kind, Some(VoiceModeType::PushToTalk));
+        assert_eq!(
+            mode.shortcut.as_ref().unwrap()[0].name.as_deref(),
+            Some("i")
+        );
+        assert_eq!(settings.mute, Some(true));
+        assert_eq!(serde_json::to_value(&settings).unwrap(), json);
+
+        // Only what was set is sent
+        assert_eq!(
+            serde_json::to_value(VoiceSettings::new().deaf(true).input(|io| io.volume(50.0)))
+                .unwrap(),
+            serde_json::json!({ "input": { "volume": 50.0 }, "deaf": true })
+        );
+    }
+}
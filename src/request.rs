@@ -0,0 +1,287 @@
+use crate::{
+    error::{DiscordError, Result},
+    event_handler::HandlerRegistry,
+    models::{
+        commands::{
+            AuthenticateArgs, AuthenticateResponse, AuthorizeArgs, AuthorizeResponse, Subscription,
+            SubscriptionArgs,
+        },
+        guild::{
+            Channel, GetChannelArgs, GetChannelsArgs, GetChannelsResponse, GetGuildArgs,
+            GetGuildsResponse, Guild, PartialChannel, PartialGuild,
+        },
+        message::Message,
+        payload::Payload,
+        rich_presence::{
+            Activity, CloseActivityRequestArgs, SendActivityJoinInviteArgs, SetActivityArgs,
+        },
+        voice::{SelectTextChannelArgs, SelectVoiceChannelArgs, UserVoiceSettings, VoiceSettings},
+        Command, ErrorEvent, Event, OpCode,
+    },
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// A command to send to Discord, along with how to read its response
+///
+/// Built the same way for both clients, which only differ in how they send the message and wait for the response.
+pub(crate) struct Request<T> {
+    pub(crate) cmd: Command,
+    /// The nonce Discord sends the response with
+    pub(crate) nonce: String,
+    pub(crate) message: Message,
+    pub(crate) response: Response<T>,
+}
+
+/// Reads the response to a [`Request`] into what the client returns
+pub(crate) struct Response<T>(fn(Payload<Value>) -> Result<T>);
+
+impl<T> Request<T> {
+    fn new<A>(
+        cmd: Command,
+        args: A,
+        evt: Option<Event>,
+        read: fn(Payload<Value>) -> Result<T>,
+    ) -> Result<Self>
+    where
+        A: Serialize,
+    {
+        let payload = Payload::with_nonce(cmd, Some(args), None, evt);
+        let nonce = into_error!(payload.nonce.clone())?;
+        let message = Message::new(OpCode::Frame, payload)?;
+
+        Ok(Self {
+            cmd,
+            nonce,
+            message,
+            response: Response(read),
+        })
+    }
+}
+
+impl<T> Response<T> {
+    /// Reads the message Discord sent in response to the command
+    pub(crate) fn read(&self, message: &Message) -> Result<T> {
+        let payload: Payload<Value> = serde_json::from_str(&message.payload)?;
+
+        // Checked before reading the data, as an error has none of the fields of the response
+        if payload.evt == Some(Event::Error) {
+            let ErrorEvent { code, message } = optional_data(payload)?.unwrap_or_default();
+
+            return Err(DiscordError::CommandFailed {
+                code: code.unwrap_or_default(),
+                message: message.unwrap_or_default(),
+            });
+        }
+
+        (self.0)(payload)
+    }
+}
+
+/// The whole response, with its args and data as `E`
+fn payload<E>(payload: Payload<Value>) -> Result<Payload<E>>
+where
+    E: Serialize + DeserializeOwned,
+{
+    let Payload {
+        cmd,
+        args,
+        data,
+        evt,
+        nonce,
+    } = payload;
+
+    Ok(Payload {
+        cmd,
+        args: args.map(serde_json::from_value).transpose()?,
+        data: data.map(serde_json::from_value).transpose()?,
+        evt,
+        nonce,
+    })
+}
+
+/// Only the data of the response, which Discord must have sent
+fn data<E>(payload: Payload<Value>) -> Result<E>
+where
+    E: DeserializeOwned,
+{
+    Ok(serde_json::from_value(into_error!(payload.data)?)?)
+}
+
+/// Only the data of the response, which may be `null`
+fn optional_data<E>(payload: Payload<Value>) -> Result<Option<E>>
+where
+    E: DeserializeOwned,
+{
+    Ok(payload.data.map(serde_json::from_value).transpose()?)
+}
+
+/// Args for the commands that take none
+fn no_args() -> Value {
+    Value::Object(Map::new())
+}
+
+pub(crate) fn set_activity(args: SetActivityArgs) -> Result<Request<Payload<Activity>>> {
+    Request::new(Command::SetActivity, args, None, payload)
+}
+
+pub(crate) fn send_activity_join_invite(user_id: u64) -> Result<Request<Payload<Value>>> {
+    Request::new(
+        Command::SendActivityJoinInvite,
+        SendActivityJoinInviteArgs::new(user_id),
+        None,
+        payload,
+    )
+}
+
+pub(crate) fn close_activity_request(user_id: u64) -> Result<Request<Payload<Value>>> {
+    Request::new(
+        Command::CloseActivityRequest,
+        CloseActivityRequestArgs::new(user_id),
+        None,
+        payload,
+    )
+}
+
+pub(crate) fn authorize(args: AuthorizeArgs) -> Result<Request<String>> {
+    Request::new(Command::Authorize, args, None, |response| {
+        Ok(data::<AuthorizeResponse>(response)?.code)
+    })
+}
+
+pub(crate) fn authenticate(access_token: String) -> Result<Request<AuthenticateResponse>> {
+    Request::new(
+        Command::Authenticate,
+        AuthenticateArgs::new(access_token),
+        None,
+        data,
+    )
+}
+
+pub(crate) fn get_guild(guild_id: u64) -> Result<Request<Guild>> {
+    Request::new(Command::GetGuild, GetGuildArgs::new(guild_id), None, data)
+}
+
+pub(crate) fn get_guilds() -> Result<Request<Vec<PartialGuild>>> {
+    Request::new(Command::GetGuilds, no_args(), None, |response| {
+        Ok(data::<GetGuildsResponse>(response)?.guilds)
+    })
+}
+
+pub(crate) fn get_channel(channel_id: u64) -> Result<Request<Channel>> {
+    Request::new(
+        Command::GetChannel,
+        GetChannelArgs::new(channel_id),
+        None,
+        data,
+    )
+}
+
+pub(crate) fn get_channels(guild_id: u64) -> Result<Request<Vec<PartialChannel>>> {
+    Request::new(
+        Command::GetChannels,
+        GetChannelsArgs::new(guild_id),
+        None,
+        |response| Ok(data::<GetChannelsResponse>(response)?.channels),
+    )
+}
+
+pub(crate) fn select_voice_channel(
+    args: SelectVoiceChannelArgs,
+) -> Result<Request<Option<Channel>>> {
+    Request::new(Command::SelectVoiceChannel, args, None, optional_data)
+}
+
+pub(crate) fn get_selected_voice_channel() -> Result<Request<Option<Channel>>> {
+    Request::new(
+        Command::GetSelectedVoiceChannel,
+        no_args(),
+        None,
+        optional_data,
+    )
+}
+
+pub(crate) fn select_text_channel(args: SelectTextChannelArgs) -> Result<Request<Option<Channel>>> {
+    Request::new(Command::SelectTextChannel, args, None, optional_data)
+}
+
+pub(crate) fn get_voice_settings() -> Result<Request<VoiceSettings>> {
+    Request::new(Command::GetVoiceSettings, no_args(), None, data)
+}
+
+pub(crate) fn set_voice_settings(settings: VoiceSettings) -> Result<Request<VoiceSettings>> {
+    Request::new(Command::SetVoiceSettings, settings, None, data)
+}
+
+pub(crate) fn set_user_voice_settings(
+    user_id: u64,
+    settings: UserVoiceSettings,
+) -> Result<Request<UserVoiceSettings>> {
+    Request::new(
+        Command::SetUserVoiceSettings,
+        settings.user_id(user_id.to_string()),
+        None,
+        data,
+    )
+}
+
+pub(crate) fn subscribe(
+    evt: Event,
+    args: SubscriptionArgs,
+) -> Result<Request<Payload<Subscription>>> {
+    Request::new(Command::Subscribe, args, Some(evt), payload)
+}
+
+pub(crate) fn unsubscribe(
+    evt: Event,
+    args: SubscriptionArgs,
+) -> Result<Request<Payload<Subscription>>> {
+    Request::new(Command::Unsubscribe, args, Some(evt), payload)
+}
+
+/// The fields of a message needed to route it
+///
+/// Unlike [`Payload`], the event is read as the name Discord sent,
+/// so that events the crate does not know still reach the raw handlers.
+#[derive(Deserialize)]
+struct Envelope {
+    evt: Option<String>,
+    nonce: Option<String>,
+    data: Option<Value>,
+}
+
+/// Routes a message from Discord to the command waiting for it, or to the event handlers
+///
+/// `respond` hands a response to the command waiting for its nonce, returning `false` if none is.
+pub(crate) fn route_message<F>(
+    msg: Message,
+    event_handler_registry: &HandlerRegistry,
+    respond: F,
+) -> Result<()>
+where
+    F: FnOnce(&str, Message) -> bool,
+{
+    let envelope: Envelope = serde_json::from_str(&msg.payload)?;
+
+    trace!("Received payload");
+
+    if let Some(nonce) = envelope.nonce {
+        trace!("Got response");
+
+        if !respond(&nonce, msg) {
+            warn!("Dropping response with unknown nonce: {}", nonce);
+        }
+    } else if let Some(name) = envelope.evt {
+        trace!("Got event");
+        let data = into_error!(envelope.data)?;
+
+        match serde_json::from_value::<Event>(Value::String(name.clone())) {
+            Ok(event) => event_handler_registry.handle(event, event.parse_data(data)),
+            Err(_) => event_handler_registry.handle_unknown(name, data),
+        }
+    } else {
+        warn!("Dropping message without nonce or event: {:?}", msg);
+    }
+
+    Ok(())
+}
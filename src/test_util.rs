@@ -0,0 +1,429 @@
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{ErrorKind, Read, Write},
+    net::Shutdown,
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use parking_lot::{Condvar, Mutex};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{
+    models::{payload::Payload, Command, Event, Message, OpCode, PartialUser, ReadyEvent},
+    utils, ClientBuilder, DiscordError, Result,
+};
+
+/// How often blocked threads of the server check whether it was dropped
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How [`MockDiscordServer`] replies to a command, see [`MockDiscordServer::respond`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    /// Reply with this data
+    Data(Value),
+    /// Reply with an `ERROR` event, as Discord does when a command fails
+    Error {
+        /// The error code
+        code: u32,
+        /// The error message
+        message: String,
+    },
+    /// Never reply, e.g. to test timeouts
+    Ignore,
+}
+
+type Responder = dyn Fn(&Payload<Value>) -> Reply + Send + Sync;
+
+#[derive(Default)]
+struct State {
+    handshakes: Vec<Value>,
+    closes: Vec<Value>,
+    received: Vec<Payload<Value>>,
+    responders: HashMap<Command, Arc<Responder>>,
+    ready: ReadyEvent,
+    /// The connection of the latest client, to dispatch events to
+    client: Option<UnixStream>,
+}
+
+#[derive(Default)]
+struct Shared {
+    state: Mutex<State>,
+    /// Notified whenever a handshake, payload or close frame is received
+    received: Condvar,
+    stopped: AtomicBool,
+}
+
+/// A fake Discord server listening on a temporary IPC socket, to test code using a [`Client`](crate::Client) without Discord
+///
+/// It completes handshakes, answers pings, replies to commands, acknowledges close frames, and records every payload it receives.
+/// Replies can be scripted per command with [`MockDiscordServer::respond`],
+/// and events sent with [`MockDiscordServer::dispatch`].
+///
+/// Only available on Unix, with the `test-util` feature.
+///
+/// # Examples
+///
+/// ```
+/// # use discord_presence::{models::Command, test_util::MockDiscordServer};
+/// # use std::time::Duration;
+/// let server = MockDiscordServer::start().unwrap();
+/// let mut drpc = server.client(1003450375732482138).build();
+/// drpc.start();
+///
+/// assert!(server.wait_for_handshakes(1, Duration::from_secs(5)));
+/// # while !drpc.is_ready() { std::thread::sleep(Duration::from_millis(10)); }
+/// drpc.set_activity(|act| act.state("testing")).unwrap();
+///
+/// let sent = server.wait_for_command(Command::SetActivity, Duration::from_secs(5)).unwrap();
+/// assert_eq!(sent.args.unwrap()["activity"]["state"], "testing");
+/// ```
+pub struct MockDiscordServer {
+    dir: PathBuf,
+    path: PathBuf,
+    shared: Arc<Shared>,
+    accept_thread: Option<JoinHandle<()>>,
+}
+
+impl MockDiscordServer {
+    /// Starts listening on a new socket in the temporary directory
+    ///
+    /// # Errors
+    /// - The socket could not be created
+    pub fn start() -> Result<Self> {
+        let dir = env::temp_dir().join(format!("discord-presence-mock-{}", utils::nonce()));
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join("discord-ipc-0");
+        let listener = UnixListener::bind(&path)?;
+        // Polled, so that the accept thread notices when the server is dropped
+        listener.set_nonblocking(true)?;
+
+        let shared = Arc::new(Shared::default());
+        shared.state.lock().ready = ReadyEvent::new()
+            .v(1)
+            .user(PartialUser::new().id("1").username("mock"));
+        let accept_thread = {
+            let shared = shared.clone();
+            thread::Builder::new()
+                .name("discord-presence-mock-server".to_owned())
+                .spawn(move || accept_loop(&listener, &shared))?
+        };
+
+        Ok(Self {
+            dir,
+            path,
+            shared,
+            accept_thread: Some(accept_thread),
+        })
+    }
+
+    #[must_use]
+    /// The path of the socket, to pass to [`ClientBuilder::ipc_path`]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    #[must_use]
+    /// A [`ClientBuilder`] connecting to this server
+    pub fn client(&self, client_id: u64) -> ClientBuilder {
+        ClientBuilder::new(client_id).ipc_path(&self.path)
+    }
+
+    /// Sets the data of the `READY` event sent after each handshake
+    ///
+    /// Defaults to a user with the id `1` and the username `mock`.
+    pub fn set_ready_data(&self, ready: ReadyEvent) {
+        self.shared.state.lock().ready = ready;
+    }
+
+    /// Replies to every `command` with what `responder` returns for the received payload
+    ///
+    /// Without a responder, `SET_ACTIVITY` replies with the activity that was set,
+    /// `SUBSCRIBE` and `UNSUBSCRIBE` with the event, and every other command with no data.
+    pub fn respond<F>(&self, command: Command, responder: F)
+    where
+        F: Fn(&Payload<Value>) -> Reply + Send + Sync + 'static,
+    {
+        self.shared
+            .state
+            .lock()
+            .responders
+            .insert(command, Arc::new(responder));
+    }
+
+    /// Sends an event to the connected client
+    ///
+    /// `event` is usually an [`Event`], or a name such as `"LOBBY_UPDATE"` to send an event the crate does not know.
+    ///
+    /// # Errors
+    /// - [`DiscordError::ConnectionClosed`] if no client is connected
+    /// - The event could not be sent
+    pub fn dispatch<E, T>(&self, event: E, data: T) -> Result<()>
+    where
+        E: Serialize,
+        T: Serialize,
+    {
+        let message = Message::new(
+            OpCode::Frame,
+            json!({ "cmd": Command::Dispatch, "evt": event, "data": data, "nonce": null }),
+        )?;
+
+        match &mut self.shared.state.lock().client {
+            Some(client) => send(client, &message),
+            None => Err(DiscordError::ConnectionClosed),
+        }
+    }
+
+    /// Closes the connection to the client, as if Discord was closed
+    pub fn disconnect(&self) {
+        if let Some(client) = self.shared.state.lock().client.take() {
+            let _ = client.shutdown(Shutdown::Both);
+        }
+    }
+
+    #[must_use]
+    /// The payloads of every handshake received so far
+    pub fn handshakes(&self) -> Vec<Value> {
+        self.shared.state.lock().handshakes.clone()
+    }
+
+    #[must_use]
+    /// The payloads of every close frame received so far
+    pub fn closes(&self) -> Vec<Value> {
+        self.shared.state.lock().closes.clone()
+    }
+
+    #[must_use]
+    /// Every payload received so far, except for handshakes and close frames
+    pub fn received(&self) -> Vec<Payload<Value>> {
+        self.shared.state.lock().received.clone()
+    }
+
+    #[must_use]
+    /// Waits until `count` handshakes were received, returning whether they were in time
+    pub fn wait_for_handshakes(&self, count: usize, timeout: Duration) -> bool {
+        self.wait_until(timeout, |state| state.handshakes.len() >= count)
+    }
+
+    #[must_use]
+    /// Waits until `command` was received, returning the latest payload for it
+    pub fn wait_for_command(&self, command: Command, timeout: Duration) -> Option<Payload<Value>> {
+        let latest = |state: &State| {
+            state
+                .received
+                .iter()
+                .rev()
+                .find(|payload| payload.cmd == command)
+                .cloned()
+        };
+
+        if self.wait_until(timeout, |state| latest(state).is_some()) {
+            latest(&self.shared.state.lock())
+        } else {
+            None
+        }
+    }
+
+    #[must_use]
+    /// Waits until the payloads received so far match `predicate`, returning whether they did in time
+    pub fn wait_for<F>(&self, timeout: Duration, predicate: F) -> bool
+    where
+        F: Fn(&[Payload<Value>]) -> bool,
+    {
+        self.wait_until(timeout, |state| predicate(&state.received))
+    }
+
+    fn wait_until<F>(&self, timeout: Duration, done: F) -> bool
+    where
+        F: Fn(&State) -> bool,
+    {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.state.lock();
+
+        while !done(&state) {
+            if self
+                .shared
+                .received
+                .wait_until(&mut state, deadline)
+                .timed_out()
+            {
+                return done(&state);
+            }
+        }
+
+        true
+    }
+}
+
+impl Drop for MockDiscordServer {
+    fn drop(&mut self) {
+        self.shared.stopped.store(true, Ordering::SeqCst);
+        self.disconnect();
+
+        if let Some(accept_thread) = self.accept_thread.take() {
+            let _ = accept_thread.join();
+        }
+
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn accept_loop(listener: &UnixListener, shared: &Arc<Shared>) {
+    while !shared.stopped.load(Ordering::SeqCst) {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let shared = shared.clone();
+                let spawned = thread::Builder::new()
+                    .name("discord-presence-mock-connection".to_owned())
+                    .spawn(move || {
+                        if let Err(why) = serve(stream, &shared) {
+                            trace!("Mock server connection closed: {}", why);
+                        }
+                    });
+
+                if spawned.is_err() {
+                    error!("Failed to spawn mock server connection thread");
+                }
+            }
+            Err(why) if why.kind() == ErrorKind::WouldBlock => thread::sleep(POLL_INTERVAL),
+            Err(why) => {
+                error!("Mock server failed to accept a connection: {}", why);
+                return;
+            }
+        }
+    }
+}
+
+/// Handles the messages of one client, until it disconnects
+fn serve(mut stream: UnixStream, shared: &Shared) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(POLL_INTERVAL))?;
+    shared.state.lock().client = Some(stream.try_clone()?);
+
+    let mut buffer = BytesMut::new();
+    let mut chunk = [0; 4096];
+
+    while !shared.stopped.load(Ordering::SeqCst) {
+        while let Some(message) = Message::decode_frame(&mut buffer)? {
+            let closing = message.opcode == OpCode::Close;
+
+            if let Some(reply) = handle(message, shared)? {
+                send(&mut stream, &reply)?;
+            }
+
+            if closing {
+                stream.shutdown(Shutdown::Both)?;
+                return Err(DiscordError::ConnectionClosed);
+            }
+        }
+
+        match stream.read(&mut chunk) {
+            Ok(0) => return Err(DiscordError::ConnectionClosed),
+            Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+            Err(why) if matches!(why.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(why) => return Err(why.into()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Records a message from the client, returning the reply to send, if any
+fn handle(message: Message, shared: &Shared) -> Result<Option<Message>> {
+    match message.opcode {
+        OpCode::Handshake => {
+            let ready = {
+                let mut state = shared.state.lock();
+                state
+                    .handshakes
+                    .push(serde_json::from_str(&message.payload)?);
+                state.ready.clone()
+            };
+            shared.received.notify_all();
+
+            Message::new(
+                OpCode::Frame,
+                json!({ "cmd": Command::Dispatch, "evt": Event::Ready, "data": ready, "nonce": null }),
+            )
+            .map(Some)
+        }
+        OpCode::Frame => {
+            let payload: Payload<Value> = serde_json::from_str(&message.payload)?;
+            let responder = {
+                let mut state = shared.state.lock();
+                state.received.push(payload.clone());
+                state.responders.get(&payload.cmd).cloned()
+            };
+            shared.received.notify_all();
+
+            let reply = match responder {
+                Some(responder) => responder(&payload),
+                None => default_reply(&payload),
+            };
+
+            let response = match reply {
+                Reply::Data(data) => {
+                    json!({ "cmd": payload.cmd, "data": data, "evt": null, "nonce": payload.nonce })
+                }
+                Reply::Error { code, message } => json!({
+                    "cmd": payload.cmd,
+                    "data": { "code": code, "message": message },
+                    "evt": Event::Error,
+                    "nonce": payload.nonce
+                }),
+                Reply::Ignore => return Ok(None),
+            };
+
+            Message::new(OpCode::Frame, response).map(Some)
+        }
+        OpCode::Ping => Ok(Some(Message {
+            opcode: OpCode::Pong,
+            payload: message.payload,
+        })),
+        OpCode::Close => {
+            shared
+                .state
+                .lock()
+                .closes
+                .push(serde_json::from_str(&message.payload)?);
+            shared.received.notify_all();
+
+            // Acknowledged as Discord does, with a close frame of its own
+            Message::new(
+                OpCode::Close,
+                json!({ "code": 1000, "message": "Connection closed" }),
+            )
+            .map(Some)
+        }
+        OpCode::Pong => Ok(None),
+    }
+}
+
+fn default_reply(payload: &Payload<Value>) -> Reply {
+    let args = payload.args.as_ref();
+
+    match payload.cmd {
+        Command::SetActivity => Reply::Data(
+            args.and_then(|args| args.get("activity"))
+                .cloned()
+                .unwrap_or(Value::Null),
+        ),
+        Command::Subscribe | Command::Unsubscribe => Reply::Data(json!({ "evt": payload.evt })),
+        _ => Reply::Data(Value::Null),
+    }
+}
+
+fn send(stream: &mut UnixStream, message: &Message) -> Result<()> {
+    stream.write_all(&message.encode()?)?;
+    Ok(())
+}
@@ -7,3 +7,8 @@ pub fn pid() -> u32 {
 pub fn nonce() -> String {
     Uuid::new_v4().to_string()
 }
+
+/// A random number below `max`, or `0` if `max` is `0`
+pub fn random_below(max: u64) -> u64 {
+    Uuid::new_v4().as_u64_pair().0 % max.max(1)
+}
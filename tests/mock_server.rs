@@ -0,0 +1,321 @@
+#![cfg(unix)]
+
+use std::{
+    os::unix::net::UnixStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+use discord_presence::{
+    models::Command,
+    test_util::{MockDiscordServer, Reply},
+    Client, DiscordError, Event, ReconnectPolicy, StreamTransport,
+};
+use serde_json::json;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends a command, discarding what Discord responds with
+type SendCommand = fn(&mut Client) -> discord_presence::Result<()>;
+
+fn ready_client(server: &MockDiscordServer, client: discord_presence::ClientBuilder) -> Client {
+    let mut client = client.build();
+    client.start();
+
+    assert!(server.wait_for_handshakes(1, TIMEOUT));
+    while !client.is_ready() {
+        thread::sleep(Duration::from_millis(10));
+    }
+
+    client
+}
+
+#[test]
+fn records_handshake_and_commands() {
+    let server = MockDiscordServer::start().unwrap();
+    let mut client = ready_client(&server, server.client(42));
+
+    client
+        .set_activity(|act| act.state("testing").details("mock server"))
+        .unwrap();
+    client.shutdown().unwrap();
+
+    assert_eq!(server.handshakes()[0]["client_id"], "42");
+
+    let received = server.received();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].cmd, Command::SetActivity);
+    assert_eq!(
+        received[0].args.as_ref().unwrap()["activity"],
+        json!({ "state": "testing", "details": "mock server" })
+    );
+}
+
+#[test]
+fn dispatches_events_to_handlers() {
+    let server = MockDiscordServer::start().unwrap();
+    let mut client = ready_client(&server, server.client(42));
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let _join = client.on_activity_join(move |join| tx.send(join.secret).unwrap());
+
+    client.subscribe(Event::ActivityJoin, |sub| sub).unwrap();
+    server
+        .dispatch(Event::ActivityJoin, json!({ "secret": "let me in" }))
+        .unwrap();
+
+    assert_eq!(
+        rx.recv_timeout(TIMEOUT).unwrap().as_deref(),
+        Some("let me in")
+    );
+    assert_eq!(
+        server.received()[0].evt,
+        Some(Event::ActivityJoin),
+        "subscribed to the event"
+    );
+    client.shutdown().unwrap();
+}
+
+#[test]
+fn dispatches_unknown_events_to_raw_handlers() {
+    let server = MockDiscordServer::start().unwrap();
+    let client = ready_client(&server, server.client(42));
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let _raw = client.on_raw_event(move |name, data| tx.send((name.to_owned(), data)).unwrap());
+
+    server
+        .dispatch("LOBBY_UPDATE", json!({ "id": "1" }))
+        .unwrap();
+
+    assert_eq!(
+        rx.recv_timeout(TIMEOUT).unwrap(),
+        ("LOBBY_UPDATE".to_owned(), json!({ "id": "1" }))
+    );
+    client.shutdown().unwrap();
+}
+
+#[test]
+fn handles_every_buffered_event_at_once() {
+    let server = MockDiscordServer::start().unwrap();
+    let mut client = ready_client(&server, server.client(42));
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let _join = client.on_activity_join(move |join| tx.send(join.secret).unwrap());
+
+    for n in 0..40 {
+        server
+            .dispatch(Event::ActivityJoin, json!({ "secret": n.to_string() }))
+            .unwrap();
+    }
+    let started = Instant::now();
+    client.set_activity(|act| act.state("testing")).unwrap();
+
+    // Rather than two events every second, which would take 20 seconds
+    assert!(started.elapsed() < Duration::from_secs(2));
+    for n in 0..40 {
+        assert_eq!(rx.recv_timeout(TIMEOUT).unwrap(), Some(n.to_string()));
+    }
+    client.shutdown().unwrap();
+}
+
+#[test]
+fn scripted_replies_fail_commands() {
+    let server = MockDiscordServer::start().unwrap();
+    let mut client = ready_client(&server, server.client(42));
+
+    server.respond(Command::SetActivity, |_| Reply::Error {
+        code: 4000,
+        message: "invalid activity".to_owned(),
+    });
+    assert!(matches!(
+        client.set_activity(|act| act.state("testing")),
+        Err(DiscordError::CommandFailed { code: 4000, message }) if message == "invalid activity"
+    ));
+
+    server.respond(Command::SetActivity, |_| Reply::Ignore);
+    assert!(matches!(
+        client.set_activity_with_timeout(|act| act.state("testing"), Duration::from_millis(200)),
+        Err(DiscordError::TimeoutError(_))
+    ));
+    client.shutdown().unwrap();
+}
+
+#[test]
+fn commands_return_discord_errors() {
+    let server = MockDiscordServer::start().unwrap();
+    let mut client = ready_client(&server, server.client(42));
+
+    let commands: [(Command, SendCommand); 11] = [
+        (Command::Authorize, |client| {
+            client.authorize(["rpc"]).map(drop)
+        }),
+        (Command::GetGuild, |client| client.get_guild(1).map(drop)),
+        (Command::GetGuilds, |client| client.get_guilds().map(drop)),
+        (Command::GetChannel, |client| {
+            client.get_channel(2).map(drop)
+        }),
+        (Command::GetChannels, |client| {
+            client.get_channels(1).map(drop)
+        }),
+        (Command::SelectVoiceChannel, |client| {
+            client.select_voice_channel(Some(1)).map(drop)
+        }),
+        (Command::SelectTextChannel, |client| {
+            client.select_text_channel(Some(2)).map(drop)
+        }),
+        (Command::GetSelectedVoiceChannel, |client| {
+            client.get_selected_voice_channel().map(drop)
+        }),
+        (Command::GetVoiceSettings, |client| {
+            client.get_voice_settings().map(drop)
+        }),
+        (Command::SetVoiceSettings, |client| {
+            client
+                .set_voice_settings(|voice| voice.mute(true))
+                .map(drop)
+        }),
+        (Command::SetUserVoiceSettings, |client| {
+            client
+                .set_user_voice_settings(3, |user| user.mute(true))
+                .map(drop)
+        }),
+    ];
+
+    for (cmd, send) in commands {
+        server.respond(cmd, |_| Reply::Error {
+            code: 4006,
+            message: "Not authenticated or invalid scope".to_owned(),
+        });
+
+        assert!(
+            matches!(
+                send(&mut client),
+                Err(DiscordError::CommandFailed { code: 4006, message })
+                    if message == "Not authenticated or invalid scope"
+            ),
+            "{:?} did not fail",
+            cmd
+        );
+    }
+    client.shutdown().unwrap();
+}
+
+#[test]
+fn restores_activity_after_reconnecting() {
+    let server = MockDiscordServer::start().unwrap();
+    let policy = ReconnectPolicy::Fixed {
+        interval: Duration::from_millis(50),
+        max_attempts: None,
+    };
+    let mut client = ready_client(&server, server.client(42).reconnect_policy(policy));
+
+    client.set_activity(|act| act.state("testing")).unwrap();
+    server.disconnect();
+
+    assert!(server.wait_for_handshakes(2, TIMEOUT));
+    assert!(server.wait_for(TIMEOUT, |received| {
+        received
+            .iter()
+            .filter(|payload| payload.cmd == Command::SetActivity)
+            .count()
+            == 2
+    }));
+    client.shutdown().unwrap();
+}
+
+#[test]
+fn authenticates_before_restoring_subscriptions() {
+    let server = MockDiscordServer::start().unwrap();
+    let policy = ReconnectPolicy::Fixed {
+        interval: Duration::from_millis(50),
+        max_attempts: None,
+    };
+    let mut client = ready_client(&server, server.client(42).reconnect_policy(policy));
+    let (tx, rx) = crossbeam_channel::unbounded();
+    let _error = client.on_error(move |error| tx.send(error.code).unwrap());
+
+    server.respond(Command::Authenticate, |_| {
+        Reply::Data(json!({
+            "user": { "id": "1", "username": "ferris" },
+            "expires": "2030-01-01T00:00:00.000Z",
+            "application": { "id": "42", "name": "mock" },
+        }))
+    });
+    client.authenticate("token").unwrap();
+    client.subscribe(Event::GuildStatus, |sub| sub).unwrap();
+
+    // Fails the restored subscription, which nobody is waiting for
+    server.respond(Command::Subscribe, |_| Reply::Error {
+        code: 4006,
+        message: "Not authenticated or invalid scope".to_owned(),
+    });
+    server.disconnect();
+
+    assert!(server.wait_for_handshakes(2, TIMEOUT));
+    assert_eq!(rx.recv_timeout(TIMEOUT).unwrap(), Some(4006));
+
+    let commands: Vec<_> = server
+        .received()
+        .iter()
+        .map(|payload| payload.cmd)
+        .collect();
+    assert_eq!(
+        commands,
+        [
+            Command::Authenticate,
+            Command::Subscribe,
+            Command::Authenticate,
+            Command::Subscribe,
+        ]
+    );
+    client.shutdown().unwrap();
+}
+
+#[test]
+fn connects_with_custom_transport() {
+    let server = MockDiscordServer::start().unwrap();
+    let path = server.path().to_path_buf();
+    let client = Client::builder(42)
+        // Searches nowhere, so that only the connector can reach the server
+        .ipc_path(server.path().join("missing"))
+        .connector(move || {
+            let stream = UnixStream::connect(&path)?;
+            stream.set_nonblocking(true)?;
+            Ok(StreamTransport::new(stream))
+        });
+    let mut client = ready_client(&server, client);
+
+    client.set_activity(|act| act.state("bridged")).unwrap();
+    client.shutdown().unwrap();
+
+    assert_eq!(server.handshakes()[0]["client_id"], "42");
+    assert_eq!(server.received()[0].cmd, Command::SetActivity);
+}
+
+#[test]
+fn closes_gracefully_on_shutdown() {
+    let server = MockDiscordServer::start().unwrap();
+
+    for clear in [false, true] {
+        let client = server.client(42).clear_activity_on_shutdown(clear);
+        let mut client = ready_client(&server, client);
+        client.set_activity(|act| act.state("testing")).unwrap();
+        client.shutdown().unwrap();
+    }
+
+    assert_eq!(server.closes().len(), 2);
+    assert_eq!(server.closes()[0]["code"], 1000);
+
+    let activities: Vec<_> = server
+        .received()
+        .into_iter()
+        .map(|payload| payload.args.unwrap().get("activity").cloned())
+        .collect();
+    assert_eq!(
+        activities,
+        vec![
+            Some(json!({ "state": "testing" })),
+            Some(json!({ "state": "testing" })),
+            // Cleared by the second client
+            None,
+        ]
+    );
+}